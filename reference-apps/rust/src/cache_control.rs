@@ -0,0 +1,90 @@
+//! Default `Cache-Control` per route, so intermediaries never cache health
+//! results. Handlers that set their own header (`/info/build`,
+//! `/info/uptime`) keep it; everything else matching a prefix in
+//! [`DEFAULTS`] gets that policy.
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderValue, CACHE_CONTROL};
+use actix_web::middleware::Next;
+use actix_web::Error;
+
+/// Path prefix (matched on whole segments) and its `Cache-Control` value.
+/// The health entries are [`crate::routes::HEALTH_PREFIXES`].
+const DEFAULTS: &[(&str, &str)] = &[
+    ("/health", "no-store"),
+    ("/v1/health", "no-store"),
+    ("/metrics", "no-store"),
+];
+
+fn default_for(path: &str) -> Option<&'static str> {
+    DEFAULTS.iter().find_map(|(prefix, value)| {
+        let rest = path.strip_prefix(prefix)?;
+        (rest.is_empty() || rest.starts_with('/')).then_some(*value)
+    })
+}
+
+pub async fn apply_defaults(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let policy = default_for(req.path());
+    let mut resp = next.call(req).await?.map_into_boxed_body();
+    if let Some(policy) = policy {
+        if !resp.headers().contains_key(CACHE_CONTROL) {
+            resp.headers_mut()
+                .insert(CACHE_CONTROL, HeaderValue::from_static(policy));
+        }
+    }
+    Ok(resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::{test, web, App, HttpResponse};
+
+    async fn cached() -> HttpResponse {
+        HttpResponse::Ok()
+            .insert_header((CACHE_CONTROL, "public, max-age=60"))
+            .finish()
+    }
+
+    #[actix_web::test]
+    async fn test_defaults_apply_unless_the_handler_set_one() {
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(apply_defaults))
+                .route(
+                    "/health/ready",
+                    web::get().to(HttpResponse::ServiceUnavailable),
+                )
+                .route("/health/cached", web::get().to(cached))
+                .route("/healthz", web::get().to(HttpResponse::Ok))
+                .route("/", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let cache_control = |uri| {
+            let app = &app;
+            async move {
+                let req = test::TestRequest::get().uri(uri).to_request();
+                let resp = test::call_service(app, req).await;
+                resp.headers()
+                    .get(CACHE_CONTROL)
+                    .map(|v| v.to_str().unwrap().to_string())
+            }
+        };
+        assert_eq!(
+            cache_control("/health/ready").await.as_deref(),
+            Some("no-store")
+        );
+        assert_eq!(
+            cache_control("/health/cached").await.as_deref(),
+            Some("public, max-age=60")
+        );
+        assert_eq!(cache_control("/healthz").await, None);
+        assert_eq!(cache_control("/").await, None);
+    }
+}
@@ -0,0 +1,235 @@
+//! Chaos mode (`CHAOS_ENABLED`): deliberate misbehaviour on demand, for
+//! exercising alerting and client retries. Faults are set through
+//! `PUT /debug/chaos` and can add latency to every request, fail a share of
+//! them with a 500, or force named health checks to fail. Nothing is
+//! injected until asked for, and every change is logged at `warn`.
+//!
+//! `/debug/*` is never touched, so chaos can always be turned off again.
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{get, put, web, Error, HttpResponse, Responder, ResponseError};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::checks::Check;
+use crate::error::ApiError;
+
+/// Marks responses a fault was injected into (`latency`, `error`).
+const INJECTED: HeaderName = HeaderName::from_static("x-chaos-injected");
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Faults {
+    latency: Duration,
+    error_rate: f64,
+    fail_checks: Vec<Check>,
+}
+
+impl Faults {
+    fn is_active(&self) -> bool {
+        !self.latency.is_zero() || self.error_rate > 0.0 || !self.fail_checks.is_empty()
+    }
+
+    fn to_json(&self) -> Value {
+        let fail_checks: Vec<&str> = self.fail_checks.iter().map(|c| c.name()).collect();
+        json!({
+            "active": self.is_active(),
+            "latency_ms": self.latency.as_millis() as u64,
+            "error_rate": self.error_rate,
+            "fail_checks": fail_checks,
+        })
+    }
+}
+
+/// A `PUT /debug/chaos` body. Omitted fields are off.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FaultsRequest {
+    latency_ms: u64,
+    error_rate: f64,
+    fail_checks: Vec<String>,
+}
+
+impl TryFrom<FaultsRequest> for Faults {
+    type Error = String;
+
+    fn try_from(req: FaultsRequest) -> Result<Self, Self::Error> {
+        if !(0.0..=1.0).contains(&req.error_rate) {
+            return Err(format!(
+                "error_rate must be between 0 and 1, got {}",
+                req.error_rate
+            ));
+        }
+        let mut fail_checks = Vec::new();
+        for name in &req.fail_checks {
+            let check: Check = name.parse()?;
+            if !fail_checks.contains(&check) {
+                fail_checks.push(check);
+            }
+        }
+        Ok(Faults {
+            latency: Duration::from_millis(req.latency_ms),
+            error_rate: req.error_rate,
+            fail_checks,
+        })
+    }
+}
+
+/// The faults in effect, shared by the middleware, the health checks and
+/// `/debug/chaos`. Only registered when `CHAOS_ENABLED` is on.
+#[derive(Debug, Clone, Default)]
+pub struct Chaos(Arc<RwLock<Faults>>);
+
+impl Chaos {
+    fn faults(&self) -> Faults {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn set(&self, faults: Faults) {
+        let mut current = self.0.write().unwrap_or_else(|e| e.into_inner());
+        if *current == faults {
+            return;
+        }
+        if faults.is_active() {
+            log::warn!("Chaos injection active: {}", faults.to_json());
+        } else {
+            log::warn!("Chaos injection off");
+        }
+        *current = faults;
+    }
+
+    /// Whether `check` is being forced to fail.
+    pub fn fails(&self, check: Check) -> bool {
+        let faults = self.0.read().unwrap_or_else(|e| e.into_inner());
+        faults.fail_checks.contains(&check)
+    }
+}
+
+pub async fn inject_faults(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let faults = match req.app_data::<web::Data<Chaos>>() {
+        Some(chaos) if !req.path().starts_with("/debug/") => chaos.faults(),
+        _ => Faults::default(),
+    };
+
+    if !faults.latency.is_zero() {
+        tokio::time::sleep(faults.latency).await;
+    }
+    if faults.error_rate > 0.0 && fastrand::f64() < faults.error_rate {
+        log::debug!("Chaos: failing {} {}", req.method(), req.path());
+        let mut resp = ApiError::InjectedFault.error_response();
+        resp.headers_mut()
+            .insert(INJECTED, HeaderValue::from_static("error"));
+        return Ok(req.into_response(resp));
+    }
+
+    let mut res = next.call(req).await?.map_into_boxed_body();
+    if !faults.latency.is_zero() {
+        res.headers_mut()
+            .insert(INJECTED, HeaderValue::from_static("latency"));
+    }
+    Ok(res)
+}
+
+#[get("/debug/chaos")]
+pub async fn get_chaos(chaos: web::Data<Chaos>) -> impl Responder {
+    HttpResponse::Ok().json(chaos.faults().to_json())
+}
+
+/// Replaces the faults in effect with the body's
+/// (`{"latency_ms", "error_rate", "fail_checks"}`); `{}` turns chaos off.
+#[put("/debug/chaos")]
+pub async fn put_chaos(
+    chaos: web::Data<Chaos>,
+    body: web::Bytes,
+) -> Result<HttpResponse, ApiError> {
+    let invalid = |reason: String| ApiError::InvalidJson { reason };
+    let req: FaultsRequest = serde_json::from_slice(&body).map_err(|e| invalid(e.to_string()))?;
+    chaos.set(Faults::try_from(req).map_err(invalid)?);
+    Ok(HttpResponse::Ok().json(chaos.faults().to_json()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::{test, App};
+
+    async fn ok() -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    #[actix_web::test]
+    async fn test_chaos_injection() {
+        let chaos = Chaos::default();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(chaos.clone()))
+                .wrap(from_fn(inject_faults))
+                .route("/", web::get().to(ok))
+                .service(get_chaos)
+                .service(put_chaos),
+        )
+        .await;
+        let get = || test::TestRequest::get().uri("/").to_request();
+        let put = |body: Value| {
+            test::TestRequest::put()
+                .uri("/debug/chaos")
+                .set_json(body)
+                .to_request()
+        };
+
+        // Off by default.
+        let resp = test::call_service(&app, get()).await;
+        assert_eq!(resp.status(), 200);
+        assert!(resp.headers().get(INJECTED).is_none());
+        let req = test::TestRequest::get().uri("/debug/chaos").to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["active"], false);
+
+        let body: Value = test::call_and_read_body_json(
+            &app,
+            put(json!({"error_rate": 1.0, "fail_checks": ["vault", "Vault"]})),
+        )
+        .await;
+        assert_eq!(body["active"], true);
+        assert_eq!(body["fail_checks"], json!(["vault"]));
+        assert!(chaos.fails(Check::Vault) && !chaos.fails(Check::Http));
+
+        let resp = test::call_service(&app, get()).await;
+        assert_eq!(resp.status(), 500);
+        assert_eq!(resp.headers().get(INJECTED).unwrap(), "error");
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "injected_fault");
+
+        // The control endpoint is spared, so chaos can be switched off.
+        let body: Value = test::call_and_read_body_json(&app, put(json!({"latency_ms": 20}))).await;
+        assert_eq!(body["latency_ms"], 20);
+        assert!(!chaos.fails(Check::Vault));
+        let start = std::time::Instant::now();
+        let resp = test::call_service(&app, get()).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers().get(INJECTED).unwrap(), "latency");
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        for bad in [
+            json!({"error_rate": 1.5}),
+            json!({"fail_checks": ["redis"]}),
+            json!({"latency": 5}),
+        ] {
+            let resp = test::call_service(&app, put(bad.clone())).await;
+            assert_eq!(resp.status(), 400, "{}", bad);
+        }
+        assert_eq!(chaos.faults().latency, Duration::from_millis(20));
+
+        let body: Value = test::call_and_read_body_json(&app, put(json!({}))).await;
+        assert_eq!(body["active"], false);
+    }
+}
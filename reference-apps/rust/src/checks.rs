@@ -0,0 +1,1838 @@
+//! Dependency health checks, the `/health/all` aggregate and `/health/ready`.
+//!
+//! Which checks are registered is controlled by `HEALTH_ENABLED_CHECKS` (an
+//! allowlist; unset means all) and per-check `HEALTH_<NAME>_ENABLED` flags,
+//! which win over the allowlist. Disabled checks are left out of the
+//! aggregate and readiness, but their own endpoints can still be probed.
+//!
+//! Each check runs inside its own bulkhead (`<NAME>_MAX_CONCURRENCY`, e.g.
+//! `VAULT_MAX_CONCURRENCY`): once that many probes of one dependency are in
+//! flight, further ones report `saturated` at once instead of queueing, so
+//! one slow downstream can't tie up capacity meant for the others.
+//!
+//! Checks can depend on others (`HEALTH_CHECK_DEPENDENCIES`, e.g.
+//! `grpc:vault`). They run in dependency order, and a check whose
+//! prerequisite failed is `skipped` rather than probed. Cycles are rejected
+//! at startup.
+//!
+//! Concurrent runs of the same check share one probe, so a burst of scrapes
+//! costs the dependency a single request.
+
+use std::collections::{HashMap, VecDeque};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::{get, web, HttpResponse, Responder};
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use prometheus::{IntGauge, IntGaugeVec};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tokio::sync::Semaphore;
+
+use crate::config::Config;
+use crate::error::ApiError;
+use crate::{chaos, drain, grpc_health, http_check, memory, timestamp, timings, tls_expiry, vault};
+
+/// Version of the health response schema, sent as `schema_version` in every
+/// health JSON body. Adding fields is not a breaking change; bump this when
+/// a field is removed, renamed or changes meaning.
+///
+/// - 1: initial versioned schema
+/// - 2: `/health/ready` reports `warming` until it has first been ready
+pub const HEALTH_SCHEMA_VERSION: u32 = 2;
+
+/// Bulkhead size for checks without a `<NAME>_MAX_CONCURRENCY`.
+pub const DEFAULT_MAX_CONCURRENCY: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Check {
+    Vault,
+    TlsExpiry,
+    Grpc,
+    Http,
+}
+
+impl Check {
+    pub const ALL: [Check; 4] = [Check::Vault, Check::TlsExpiry, Check::Grpc, Check::Http];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Check::Vault => "vault",
+            Check::TlsExpiry => "tls_expiry",
+            Check::Grpc => "grpc",
+            Check::Http => "http",
+        }
+    }
+
+    /// Critical checks gate readiness; the rest only degrade `/health/all`.
+    pub fn is_critical(self) -> bool {
+        match self {
+            Check::Vault => true,
+            Check::TlsExpiry | Check::Grpc | Check::Http => false,
+        }
+    }
+}
+
+impl FromStr for Check {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Check::ALL
+            .into_iter()
+            .find(|check| check.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("unknown health check '{}'", s))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Healthy,
+    Unhealthy,
+    /// The check's bulkhead was full, so it wasn't run.
+    Saturated,
+    /// A prerequisite failed, so the check wasn't run.
+    Skipped,
+    /// Timed out under a `degraded` timeout policy: slow, but not down.
+    Degraded,
+}
+
+/// What a check that overruns `HEALTH_CHECK_TIMEOUT_MS` reports, from
+/// `HEALTH_<NAME>_TIMEOUT_POLICY`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimeoutPolicy {
+    #[default]
+    Unhealthy,
+    Degraded,
+}
+
+impl TimeoutPolicy {
+    pub fn name(self) -> &'static str {
+        match self {
+            TimeoutPolicy::Unhealthy => "unhealthy",
+            TimeoutPolicy::Degraded => "degraded",
+        }
+    }
+}
+
+impl FromStr for TimeoutPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "unhealthy" => Ok(TimeoutPolicy::Unhealthy),
+            "degraded" => Ok(TimeoutPolicy::Degraded),
+            other => Err(format!("unknown timeout policy '{}'", other)),
+        }
+    }
+}
+
+/// Outcome of one check: a status plus check-specific fields.
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub status: Status,
+    #[serde(flatten)]
+    pub details: Map<String, Value>,
+}
+
+impl CheckResult {
+    fn new(status: Status) -> CheckResult {
+        CheckResult {
+            status,
+            details: Map::new(),
+        }
+    }
+
+    fn with(mut self, key: &str, value: impl Into<Value>) -> CheckResult {
+        self.details.insert(key.to_string(), value.into());
+        self
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.status == Status::Healthy
+    }
+
+    /// Healthy or only degraded: answered with 200 by the per-check routes.
+    /// Readiness still counts a degraded critical check as down.
+    pub fn is_serving(&self) -> bool {
+        matches!(self.status, Status::Healthy | Status::Degraded)
+    }
+
+    fn skipped(prerequisite: Check) -> CheckResult {
+        CheckResult::new(Status::Skipped).with(
+            "reason",
+            format!("skipped: depends on {}", prerequisite.name()),
+        )
+    }
+
+    /// The status, or for a skipped check the reason, as listed by readiness.
+    fn summary(&self) -> Value {
+        match self.details.get("reason") {
+            Some(reason) if self.status == Status::Skipped => reason.clone(),
+            _ => serde_json::to_value(self.status).unwrap_or_default(),
+        }
+    }
+}
+
+/// Orders every check so each comes after its prerequisites, or names the
+/// checks caught in a cycle.
+fn dependency_order(dependencies: &[(Check, Check)]) -> Result<Vec<Check>, String> {
+    let mut remaining: Vec<Check> = Check::ALL.to_vec();
+    let mut order = Vec::with_capacity(remaining.len());
+    while !remaining.is_empty() {
+        let (ready, blocked): (Vec<Check>, Vec<Check>) = remaining.iter().partition(|check| {
+            dependencies
+                .iter()
+                .filter(|(dependent, _)| dependent == *check)
+                .all(|(_, prerequisite)| order.contains(prerequisite))
+        });
+        if ready.is_empty() {
+            let names: Vec<&str> = blocked.iter().map(|check| check.name()).collect();
+            return Err(format!(
+                "dependency cycle among health checks: {}",
+                names.join(", ")
+            ));
+        }
+        order.extend(ready);
+        remaining = blocked;
+    }
+    Ok(order)
+}
+
+/// One `/health/all` run, as kept for `/health/history`.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEntry {
+    pub timestamp: String,
+    pub status: &'static str,
+    pub services: Map<String, Value>,
+}
+
+type Probe = Shared<BoxFuture<'static, CheckResult>>;
+
+/// The registered checks and what they need to run.
+pub struct HealthChecks {
+    config: web::Data<Config>,
+    vault_token: web::Data<vault::VaultToken>,
+    http: reqwest::Client,
+    /// The client for Vault calls, `http` unless Vault needs mutual TLS.
+    vault_http: reqwest::Client,
+    bulkheads: HashMap<Check, Bulkhead>,
+    /// Every check, prerequisites first.
+    order: Vec<Check>,
+    /// The last `HEALTH_HISTORY_SIZE` aggregate results, oldest first.
+    history: Mutex<VecDeque<HistoryEntry>>,
+    /// Probes currently running, joined by concurrent callers.
+    probes: Mutex<HashMap<Check, Probe>>,
+    /// Reported readiness and the run of evaluations disagreeing with it.
+    readiness: Mutex<(Readiness, u32)>,
+    /// `readiness_state{state}`, one-hot.
+    readiness_gauge: Option<IntGaugeVec>,
+    /// Forces checks to fail while chaos mode says so.
+    chaos: Option<chaos::Chaos>,
+}
+
+/// Where readiness stands. `Warming` is not ready yet since startup;
+/// `NotReady` is not ready after having been ready (or while draining).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Readiness {
+    Warming,
+    Ready,
+    NotReady,
+}
+
+impl Readiness {
+    const ALL: [Readiness; 3] = [Readiness::Warming, Readiness::Ready, Readiness::NotReady];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Readiness::Warming => "warming",
+            Readiness::Ready => "ready",
+            Readiness::NotReady => "not_ready",
+        }
+    }
+}
+
+impl HealthChecks {
+    /// `in_flight` is the per-dependency gauge the bulkheads keep current.
+    /// Fails if the declared check dependencies form a cycle.
+    pub fn new(
+        config: web::Data<Config>,
+        vault_token: web::Data<vault::VaultToken>,
+        http: reqwest::Client,
+        in_flight: &IntGaugeVec,
+    ) -> Result<Self, String> {
+        let order = dependency_order(&config.check_dependencies)?;
+        let bulkheads = Check::ALL
+            .into_iter()
+            .map(|check| {
+                let limit = config
+                    .check_max_concurrency
+                    .get(&check)
+                    .copied()
+                    .unwrap_or(DEFAULT_MAX_CONCURRENCY);
+                let bulkhead = Bulkhead {
+                    permits: Arc::new(Semaphore::new(limit)),
+                    limit,
+                    in_flight: in_flight.with_label_values(&[check.name()]),
+                };
+                (check, bulkhead)
+            })
+            .collect();
+        let history = Mutex::new(VecDeque::with_capacity(config.health_history_size));
+        Ok(HealthChecks {
+            config,
+            vault_token,
+            vault_http: http.clone(),
+            http,
+            bulkheads,
+            order,
+            history,
+            probes: Mutex::new(HashMap::new()),
+            readiness: Mutex::new((Readiness::Warming, 0)),
+            readiness_gauge: None,
+            chaos: None,
+        })
+    }
+
+    /// Exports each readiness evaluation to `gauge`, labelled by `state`.
+    pub fn with_readiness_gauge(mut self, gauge: IntGaugeVec) -> Self {
+        gauge.with_label_values(&[Readiness::Warming.name()]).set(1);
+        self.readiness_gauge = Some(gauge);
+        self
+    }
+
+    /// Sends the Vault checks through `http` instead of the shared client.
+    pub fn with_vault_http(mut self, http: reqwest::Client) -> Self {
+        self.vault_http = http;
+        self
+    }
+
+    /// Fails the checks `chaos` says to, without probing them.
+    pub fn with_chaos(mut self, chaos: chaos::Chaos) -> Self {
+        self.chaos = Some(chaos);
+        self
+    }
+
+    /// Records a readiness evaluation and names the state it puts us in.
+    /// The state only flips after `READINESS_FAILURE_COUNT` consecutive
+    /// failing evaluations (or `READINESS_SUCCESS_COUNT` passing ones, also
+    /// out of `warming`); draining flips it at once.
+    fn readiness_state(&self, ready: bool, draining: bool) -> Readiness {
+        let mut tracked = self.readiness.lock().unwrap_or_else(|e| e.into_inner());
+        let (reported, streak) = &mut *tracked;
+        if draining {
+            (*reported, *streak) = (Readiness::NotReady, 0);
+        } else if ready == (*reported == Readiness::Ready) {
+            *streak = 0;
+        } else {
+            *streak += 1;
+            let needed = if ready {
+                self.config.readiness_success_count
+            } else {
+                self.config.readiness_failure_count
+            };
+            if *streak >= needed.max(1) {
+                *reported = if ready {
+                    Readiness::Ready
+                } else {
+                    Readiness::NotReady
+                };
+                *streak = 0;
+            }
+        }
+        let state = *reported;
+        drop(tracked);
+        if let Some(gauge) = &self.readiness_gauge {
+            for other in Readiness::ALL {
+                gauge
+                    .with_label_values(&[other.name()])
+                    .set((other == state) as i64);
+            }
+        }
+        state
+    }
+
+    fn prerequisites(&self, check: Check) -> impl Iterator<Item = Check> + '_ {
+        self.config
+            .check_dependencies
+            .iter()
+            .filter(move |(dependent, _)| *dependent == check)
+            .map(|(_, prerequisite)| *prerequisite)
+    }
+
+    /// `checks` plus everything they transitively depend on.
+    fn with_prerequisites(&self, checks: &[Check]) -> Vec<Check> {
+        let mut all = checks.to_vec();
+        let mut i = 0;
+        while i < all.len() {
+            for prerequisite in self.prerequisites(all[i]) {
+                if !all.contains(&prerequisite) {
+                    all.push(prerequisite);
+                }
+            }
+            i += 1;
+        }
+        all
+    }
+
+    fn record(&self, entry: HistoryEntry) {
+        let size = self.config.health_history_size;
+        if size == 0 {
+            return;
+        }
+        let mut history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        while history.len() >= size {
+            history.pop_front();
+        }
+        history.push_back(entry);
+    }
+
+    /// Recorded aggregate results, newest first.
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        let history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        history.iter().rev().cloned().collect()
+    }
+
+    pub fn enabled(&self) -> &[Check] {
+        &self.config.enabled_checks
+    }
+
+    /// Runs `check`, or joins the probe of it already in flight.
+    pub async fn run(&self, check: Check) -> CheckResult {
+        let probe = {
+            let mut probes = self.probes.lock().unwrap_or_else(|e| e.into_inner());
+            probes
+                .entry(check)
+                .or_insert_with(|| self.probe(check))
+                .clone()
+        };
+        let start = Instant::now();
+        let result = probe.clone().await;
+        timings::record(check.name(), start.elapsed());
+
+        // The first caller to finish retires the probe; later runs start afresh.
+        let mut probes = self.probes.lock().unwrap_or_else(|e| e.into_inner());
+        if probes.get(&check).is_some_and(|p| p.ptr_eq(&probe)) {
+            probes.remove(&check);
+        }
+        result
+    }
+
+    /// Runs `check` in a probe of its own, never joining one in flight.
+    pub async fn run_fresh(&self, check: Check) -> CheckResult {
+        let start = Instant::now();
+        let result = self.probe(check).await;
+        timings::record(check.name(), start.elapsed());
+        result
+    }
+
+    fn probe(&self, check: Check) -> Probe {
+        let config = self.config.clone();
+        let vault_token = self.vault_token.clone();
+        let http = self.http.clone();
+        let vault_http = self.vault_http.clone();
+        let bulkhead = self.bulkheads[&check].clone();
+        let chaos = self.chaos.clone();
+        let policy = config
+            .check_timeout_policy
+            .get(&check)
+            .copied()
+            .unwrap_or_default();
+        async move {
+            if chaos.is_some_and(|chaos| chaos.fails(check)) {
+                return CheckResult::new(Status::Unhealthy)
+                    .with("error", "failure injected by chaos mode");
+            }
+            let Ok(_permit) = bulkhead.permits.try_acquire() else {
+                return CheckResult::new(Status::Saturated).with("max_concurrency", bulkhead.limit);
+            };
+            let _in_flight = InFlight::start(&bulkhead.in_flight);
+
+            let run = async {
+                match check {
+                    Check::Vault => check_vault_agent(&vault_http, &config, &vault_token).await,
+                    Check::TlsExpiry => check_tls_expiry(&config).await,
+                    Check::Grpc => check_grpc(&config).await,
+                    Check::Http => check_http(&http, &config).await,
+                }
+            };
+            if config.check_timeout_ms == 0 {
+                return run.await;
+            }
+            let timeout = Duration::from_millis(config.check_timeout_ms);
+            match tokio::time::timeout(timeout, run).await {
+                Ok(result) => result,
+                Err(_) => {
+                    let status = match policy {
+                        TimeoutPolicy::Unhealthy => Status::Unhealthy,
+                        TimeoutPolicy::Degraded => Status::Degraded,
+                    };
+                    CheckResult::new(status).with(
+                        "error",
+                        format!("timed out after {}ms", config.check_timeout_ms),
+                    )
+                }
+            }
+        }
+        .map(move |result| result.with("timeout_policy", policy.name()))
+        .boxed()
+        .shared()
+    }
+
+    /// Runs the given checks, keeping their order in the result. Checks run
+    /// concurrently once their prerequisites among `checks` are done; one
+    /// whose prerequisite didn't pass is skipped. Prerequisites not in
+    /// `checks` aren't run and don't hold anything up.
+    pub async fn run_all(&self, checks: &[Check]) -> Vec<(Check, CheckResult)> {
+        let mut results: HashMap<Check, CheckResult> = HashMap::new();
+        let mut pending: Vec<Check> = self
+            .order
+            .iter()
+            .copied()
+            .filter(|check| checks.contains(check))
+            .collect();
+
+        while !pending.is_empty() {
+            let (wave, rest): (Vec<Check>, Vec<Check>) = pending.iter().partition(|check| {
+                self.prerequisites(**check)
+                    .all(|p| !checks.contains(&p) || results.contains_key(&p))
+            });
+            let mut runnable = Vec::new();
+            for check in wave {
+                let failed = self
+                    .prerequisites(check)
+                    .find(|p| results.get(p).is_some_and(|r| !r.is_healthy()));
+                match failed {
+                    Some(prerequisite) => {
+                        results.insert(check, CheckResult::skipped(prerequisite));
+                    }
+                    None => runnable.push(check),
+                }
+            }
+            let ran =
+                futures_util::future::join_all(runnable.iter().map(|check| self.run(*check))).await;
+            results.extend(runnable.into_iter().zip(ran));
+            pending = rest;
+        }
+
+        checks
+            .iter()
+            .filter_map(|check| results.remove(check).map(|result| (*check, result)))
+            .collect()
+    }
+}
+
+/// With `VAULT_AGENT_ADDR` set, the sidecar is what the app depends on, so
+/// it is checked first and upstream Vault (unless `VAULT_AGENT_CHECK_UPSTREAM`
+/// is off) is reported separately alongside it.
+async fn check_vault_agent(
+    http: &reqwest::Client,
+    config: &Config,
+    token: &vault::VaultToken,
+) -> CheckResult {
+    let Some(agent_addr) = config.vault_agent_addr.as_deref() else {
+        return check_vault_cluster(http, config, token).await;
+    };
+
+    let upstream = async {
+        if config.vault_agent_check_upstream {
+            Some(check_vault_cluster(http, config, token).await)
+        } else {
+            None
+        }
+    };
+    let (agent, upstream) = futures_util::future::join(
+        vault::check_agent(http, agent_addr, config.vault_max_response_bytes),
+        upstream,
+    )
+    .await;
+
+    let agent = match agent {
+        Ok(ttl) => CheckResult::new(Status::Healthy)
+            .with("addr", agent_addr)
+            .with("token_ttl", ttl),
+        Err(e) => CheckResult::new(Status::Unhealthy)
+            .with("addr", agent_addr)
+            .with("error", e),
+    };
+    let healthy = agent.is_healthy() && upstream.as_ref().is_none_or(CheckResult::is_healthy);
+    let result = CheckResult::new(if healthy {
+        Status::Healthy
+    } else {
+        Status::Unhealthy
+    })
+    .with("agent", serde_json::to_value(agent).unwrap_or_default());
+    match upstream {
+        Some(upstream) => result.with(
+            "upstream",
+            serde_json::to_value(upstream).unwrap_or_default(),
+        ),
+        None => result,
+    }
+}
+
+/// A dependency's concurrency budget.
+#[derive(Clone)]
+struct Bulkhead {
+    permits: Arc<Semaphore>,
+    limit: usize,
+    in_flight: IntGauge,
+}
+
+/// Counts a probe as in flight until dropped, even if the request is
+/// cancelled mid-check.
+struct InFlight<'a>(&'a IntGauge);
+
+impl<'a> InFlight<'a> {
+    fn start(gauge: &'a IntGauge) -> InFlight<'a> {
+        gauge.inc();
+        InFlight(gauge)
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+async fn check_vault(
+    http: &reqwest::Client,
+    config: &Config,
+    token: &vault::VaultToken,
+) -> CheckResult {
+    let vault_addr = &config.vault_addr;
+    let mount = &config.vault_kv_mount;
+
+    // Standbys can serve reads (forwarding writes), so they answer 200 here
+    // and the body says which role the node has; other documented codes are
+    // mapped rather than all read as "unavailable".
+    let resp = http
+        .get(format!(
+            "{}/v1/sys/health?standbyok=true&perfstandbyok=true",
+            vault_addr
+        ))
+        .send()
+        .await
+        .ok();
+    let state = resp
+        .as_ref()
+        .and_then(|resp| vault::health_code_state(resp.status().as_u16()));
+    let role = match (state, resp) {
+        (Some(Ok(role)), Some(resp)) => {
+            vault::read_body_limited(resp, config.vault_max_response_bytes)
+                .await
+                .ok()
+                .and_then(|body| serde_json::from_slice::<Value>(&body).ok())
+                .and_then(|health| vault::health_body_role(&health))
+                .unwrap_or(role)
+        }
+        (Some(Err(reason)), _) => return CheckResult::new(Status::Unhealthy).with("error", reason),
+        _ => return CheckResult::new(Status::Unhealthy).with("error", "Vault unavailable"),
+    };
+    let healthy = || {
+        let result = CheckResult::new(Status::Healthy);
+        if role == "active" {
+            result
+        } else {
+            result.with("node_state", role)
+        }
+    };
+
+    // Listing mounts needs a token; without one only sys/health is checked.
+    let Some(token) = token.get() else {
+        return healthy();
+    };
+
+    let result = match vault::check_mount(
+        http,
+        vault_addr,
+        &token,
+        mount,
+        config.vault_max_response_bytes,
+    )
+    .await
+    {
+        Ok(()) => healthy().with("mount", mount.as_str()),
+        Err(e) => {
+            return CheckResult::new(Status::Unhealthy)
+                .with("mount", mount.as_str())
+                .with("error", e)
+        }
+    };
+
+    let result = match config.vault_capability_check_path.as_deref() {
+        Some(path) => check_vault_capabilities(http, config, &token, path, result).await,
+        None => result,
+    };
+    match config.vault_unwrap_check_path.as_deref() {
+        Some(path) => check_vault_unwrap(http, config, &token, path, result).await,
+        None => result,
+    }
+}
+
+async fn check_vault_capabilities(
+    http: &reqwest::Client,
+    config: &Config,
+    token: &str,
+    path: &str,
+    result: CheckResult,
+) -> CheckResult {
+    match vault::check_capabilities(
+        http,
+        &config.vault_addr,
+        token,
+        path,
+        config.vault_max_response_bytes,
+    )
+    .await
+    {
+        Ok(capabilities) => {
+            let missing =
+                vault::missing_capabilities(&capabilities, &config.vault_required_capabilities);
+            let status = if missing.is_empty() {
+                Status::Healthy
+            } else {
+                Status::Unhealthy
+            };
+            let mut result = CheckResult { status, ..result }
+                .with("capability_path", path)
+                .with("capabilities", capabilities);
+            if !missing.is_empty() {
+                result = result
+                    .with("missing_capabilities", missing)
+                    .with("error", "token lacks required capabilities");
+            }
+            result
+        }
+        Err(e) => CheckResult {
+            status: Status::Unhealthy,
+            ..result
+        }
+        .with("capability_path", path)
+        .with("error", e),
+    }
+}
+
+/// Reads `path` response-wrapped and unwraps it, checking the secret has
+/// `VAULT_UNWRAP_EXPECTED_KEYS`. Only key names are reported, never values.
+async fn check_vault_unwrap(
+    http: &reqwest::Client,
+    config: &Config,
+    token: &str,
+    path: &str,
+    result: CheckResult,
+) -> CheckResult {
+    let outcome = vault::check_unwrap(
+        http,
+        &config.vault_addr,
+        token,
+        path,
+        config.vault_max_response_bytes,
+    )
+    .await;
+    let mut unwrap = Map::new();
+    unwrap.insert("path".to_string(), path.into());
+    let healthy = match outcome {
+        Ok(keys) => {
+            let missing: Vec<&String> = config
+                .vault_unwrap_expected_keys
+                .iter()
+                .filter(|key| !keys.contains(key))
+                .collect();
+            let redacted: Map<String, Value> = keys
+                .iter()
+                .map(|key| (key.clone(), "<redacted>".into()))
+                .collect();
+            unwrap.insert("keys".to_string(), redacted.into());
+            if !missing.is_empty() {
+                unwrap.insert("missing_keys".to_string(), serde_json::json!(missing));
+            }
+            missing.is_empty()
+        }
+        Err(e) => {
+            unwrap.insert("error".to_string(), e.into());
+            false
+        }
+    };
+    let status = if healthy {
+        result.status
+    } else {
+        Status::Unhealthy
+    };
+    CheckResult { status, ..result }.with("unwrap", unwrap)
+}
+
+/// With `VAULT_NODES` set, `/health/vault` also probes each node directly so
+/// a sealed or down member isn't hidden behind the load-balanced address.
+async fn check_vault_cluster(
+    http: &reqwest::Client,
+    config: &Config,
+    token: &vault::VaultToken,
+) -> CheckResult {
+    let (result, nodes) = futures_util::future::join(
+        check_vault(http, config, token),
+        futures_util::future::join_all(
+            config
+                .vault_nodes
+                .iter()
+                .map(|node| vault::probe_node(http, node, config.vault_max_response_bytes)),
+        ),
+    )
+    .await;
+    if nodes.is_empty() {
+        return result;
+    }
+
+    let cluster = vault::assess_cluster(&nodes);
+    let mut result = result
+        .with("cluster", cluster)
+        .with("nodes", serde_json::to_value(nodes).unwrap_or_default());
+    if cluster == "unavailable" {
+        result.status = Status::Unhealthy;
+    }
+    result
+}
+
+async fn check_tls_expiry(config: &Config) -> CheckResult {
+    let warn_days = config.tls_expiry_warn_days;
+    let hosts = futures_util::future::join_all(
+        config
+            .tls_check_hosts
+            .iter()
+            .map(|host| tls_expiry::check_host(host, warn_days)),
+    )
+    .await;
+
+    let status = if hosts.iter().all(|h| h.is_healthy()) {
+        Status::Healthy
+    } else {
+        Status::Unhealthy
+    };
+    CheckResult::new(status)
+        .with("warn_days", warn_days)
+        .with("hosts", serde_json::to_value(hosts).unwrap_or_default())
+}
+
+async fn check_http(http: &reqwest::Client, config: &Config) -> CheckResult {
+    let endpoints = futures_util::future::join_all(
+        config
+            .http_checks
+            .iter()
+            .map(|check| http_check::probe(http, check)),
+    )
+    .await;
+
+    let status = if endpoints.iter().all(|e| e.is_healthy()) {
+        Status::Healthy
+    } else {
+        Status::Unhealthy
+    };
+    CheckResult::new(status).with(
+        "endpoints",
+        serde_json::to_value(endpoints).unwrap_or_default(),
+    )
+}
+
+async fn check_grpc(config: &Config) -> CheckResult {
+    let Some(addr) = config.grpc_health_addr.as_deref() else {
+        return CheckResult::new(Status::Unhealthy).with("error", "GRPC_HEALTH_ADDR not set");
+    };
+    let service = config.grpc_health_service.as_str();
+
+    let result = match grpc_health::check(addr, service).await {
+        Ok(serving) if serving == grpc_health::ServingStatus::Serving => {
+            CheckResult::new(Status::Healthy).with("serving_status", serving.as_str_name())
+        }
+        Ok(serving) => {
+            CheckResult::new(Status::Unhealthy).with("serving_status", serving.as_str_name())
+        }
+        Err(e) => CheckResult::new(Status::Unhealthy).with("error", e),
+    };
+    if service.is_empty() {
+        result
+    } else {
+        result.with("service", service)
+    }
+}
+
+/// Renders a single check: 200 when healthy, 503 otherwise.
+fn check_response(result: CheckResult) -> HttpResponse {
+    let mut body = Map::new();
+    body.insert("schema_version".to_string(), HEALTH_SCHEMA_VERSION.into());
+    if let Value::Object(fields) = serde_json::to_value(&result).unwrap_or_default() {
+        body.extend(fields);
+    }
+    if result.is_serving() {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+#[get("/health/vault")]
+pub async fn health_vault(checks: web::Data<HealthChecks>) -> impl Responder {
+    check_response(checks.run(Check::Vault).await)
+}
+
+#[get("/health/tls-expiry")]
+pub async fn health_tls_expiry(checks: web::Data<HealthChecks>) -> impl Responder {
+    check_response(checks.run(Check::TlsExpiry).await)
+}
+
+#[get("/health/grpc")]
+pub async fn health_grpc(checks: web::Data<HealthChecks>) -> impl Responder {
+    check_response(checks.run(Check::Grpc).await)
+}
+
+/// The `host:port` a check connects to, when it has exactly one.
+fn check_target(config: &Config, check: Check) -> Option<String> {
+    let url = match check {
+        Check::Vault => config
+            .vault_agent_addr
+            .as_deref()
+            .unwrap_or(&config.vault_addr),
+        Check::Grpc => config.grpc_health_addr.as_deref()?,
+        Check::Http => match config.http_checks.as_slice() {
+            [only] => only.url.as_str(),
+            _ => return None,
+        },
+        Check::TlsExpiry => return None,
+    };
+    let url = reqwest::Url::parse(url).ok()?;
+    Some(format!(
+        "{}:{}",
+        url.host_str()?,
+        url.port_or_known_default()?
+    ))
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+/// Times name resolution and a bare TCP connect to `target`. This is a
+/// pre-flight connection of its own, made before the check runs; the check
+/// resolves and connects again on its own client.
+async fn connect_timing(target: &str) -> Map<String, Value> {
+    let mut timing = Map::new();
+    timing.insert("target".to_string(), target.into());
+
+    let start = Instant::now();
+    let addr = tokio::net::lookup_host(target).await.map(|mut a| a.next());
+    timing.insert("dns_ms".to_string(), millis(start.elapsed()).into());
+    let addr = match addr {
+        Ok(Some(addr)) => addr,
+        Ok(None) => {
+            timing.insert("error".to_string(), "no addresses resolved".into());
+            return timing;
+        }
+        Err(e) => {
+            timing.insert(
+                "error".to_string(),
+                format!("DNS lookup failed: {}", e).into(),
+            );
+            return timing;
+        }
+    };
+
+    let start = Instant::now();
+    let connect =
+        tokio::time::timeout(Duration::from_secs(5), tokio::net::TcpStream::connect(addr)).await;
+    timing.insert("connect_ms".to_string(), millis(start.elapsed()).into());
+    let error = match connect {
+        Ok(Ok(_)) => None,
+        Ok(Err(e)) => Some(format!("connect to {} failed: {}", addr, e)),
+        Err(_) => Some(format!("connect to {} timed out", addr)),
+    };
+    if let Some(error) = error {
+        timing.insert("error".to_string(), error.into());
+    }
+    timing
+}
+
+/// Runs one check on demand, whether or not it is enabled, with a timing
+/// breakdown: the pre-flight `dns_ms`/`connect_ms`, the check's own
+/// `check_ms`, and `total_ms` for both. It runs a probe of its own rather
+/// than joining one in flight, so the result is always fresh and
+/// `?fresh=true` is accepted for clarity only.
+#[get("/health/check/{name}")]
+pub async fn health_check_one(
+    checks: web::Data<HealthChecks>,
+    name: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let name = name.into_inner();
+    let check = name
+        .parse::<Check>()
+        .map_err(|_| ApiError::UnknownCheck { name })?;
+
+    let start = Instant::now();
+    let mut timing = match check_target(&checks.config, check) {
+        Some(target) => connect_timing(&target).await,
+        None => Map::new(),
+    };
+    let check_start = Instant::now();
+    let result = checks.run_fresh(check).await;
+    timing.insert("check_ms".to_string(), millis(check_start.elapsed()).into());
+    timing.insert("total_ms".to_string(), millis(start.elapsed()).into());
+
+    let body = serde_json::json!({
+        "schema_version": HEALTH_SCHEMA_VERSION,
+        "check": check.name(),
+        "fresh": true,
+        "timing": timing,
+        "result": result
+    });
+    Ok(if result.is_serving() {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    })
+}
+
+/// Every enabled check. Always 200; `status` is `degraded` if any failed.
+#[get("/health/all")]
+pub async fn health_all(checks: web::Data<HealthChecks>) -> impl Responder {
+    let results = checks.run_all(checks.enabled()).await;
+    let healthy = results.iter().all(|(_, result)| result.is_healthy());
+    let status = if healthy { "healthy" } else { "degraded" };
+    let timestamp = timestamp::now();
+    checks.record(HistoryEntry {
+        timestamp: timestamp.clone(),
+        status,
+        services: results
+            .iter()
+            .map(|(check, result)| {
+                let status = serde_json::to_value(result.status).unwrap_or_default();
+                (check.name().to_string(), status)
+            })
+            .collect(),
+    });
+    let services: Map<String, Value> = results
+        .into_iter()
+        .map(|(check, result)| {
+            let result = serde_json::to_value(result).unwrap_or_default();
+            (check.name().to_string(), result)
+        })
+        .collect();
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "schema_version": HEALTH_SCHEMA_VERSION,
+        "status": status,
+        "timestamp": timestamp,
+        "services": services
+    }))
+}
+
+/// Recent `/health/all` results, newest first, for spotting flapping checks.
+#[get("/health/history")]
+pub async fn health_history(checks: web::Data<HealthChecks>) -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({
+        "schema_version": HEALTH_SCHEMA_VERSION,
+        "size": checks.config.health_history_size,
+        "history": checks.history()
+    }))
+}
+
+/// Resident memory against `MEMORY_READY_LIMIT_MB`, or `None` when no limit
+/// is set or RSS can't be read.
+fn memory_pressure(config: &Config) -> Option<CheckResult> {
+    let limit_bytes = config.memory_ready_limit_mb?.saturating_mul(1024 * 1024);
+    let current_bytes = memory::rss_bytes()?;
+    let status = if current_bytes > limit_bytes {
+        Status::Unhealthy
+    } else {
+        Status::Healthy
+    };
+    Some(
+        CheckResult::new(status)
+            .with("current_bytes", current_bytes)
+            .with("limit_bytes", limit_bytes),
+    )
+}
+
+/// Ready when no more than `READINESS_FAILURE_THRESHOLD` (default 0) enabled
+/// critical checks fail, listed under `down`, and resident memory is under
+/// `MEMORY_READY_LIMIT_MB` (if set). Prerequisites of critical checks
+/// are run too, and listed, so a skip can be traced. While draining for
+/// shutdown it's not ready without running any checks.
+///
+/// Not ready is reported as `warming` until readiness has passed once, and
+/// `not_ready` after that.
+///
+/// Returns whether ready and the `/health/ready` body; the gRPC health
+/// server reports the same state.
+pub async fn readiness(checks: &HealthChecks, draining: Option<&drain::Draining>) -> (bool, Value) {
+    if draining.is_some_and(drain::Draining::is_draining) {
+        let state = checks.readiness_state(false, true);
+        let body = serde_json::json!({
+            "schema_version": HEALTH_SCHEMA_VERSION,
+            "status": state.name(),
+            "draining": true
+        });
+        return (false, body);
+    }
+    let critical: Vec<Check> = checks
+        .enabled()
+        .iter()
+        .copied()
+        .filter(|check| check.is_critical())
+        .collect();
+    let results = checks.run_all(&checks.with_prerequisites(&critical)).await;
+    let memory = memory_pressure(&checks.config);
+    let down: Vec<&str> = results
+        .iter()
+        .filter(|(check, result)| critical.contains(check) && !result.is_healthy())
+        .map(|(check, _)| check.name())
+        .collect();
+    let passed = down.len() <= checks.config.readiness_failure_threshold
+        && memory.as_ref().is_none_or(CheckResult::is_healthy);
+    let statuses: Map<String, Value> = results
+        .iter()
+        .map(|(check, result)| (check.name().to_string(), result.summary()))
+        .collect();
+
+    let state = checks.readiness_state(passed, false);
+    let ready = state == Readiness::Ready;
+    let mut body = serde_json::json!({
+        "schema_version": HEALTH_SCHEMA_VERSION,
+        "status": state.name(),
+        "checks": statuses
+    });
+    if !down.is_empty() {
+        body["down"] = serde_json::json!(down);
+    }
+    if let Some(memory) = memory {
+        body["memory"] = serde_json::to_value(memory).unwrap_or_default();
+    }
+    (ready, body)
+}
+
+/// [`readiness`] over HTTP: 200 when ready, 503 otherwise.
+#[get("/health/ready")]
+pub async fn health_ready(
+    checks: web::Data<HealthChecks>,
+    draining: Option<web::Data<drain::Draining>>,
+) -> impl Responder {
+    let (ready, body) = readiness(&checks, draining.as_ref().map(|d| d.get_ref())).await;
+    if ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{self};
+    use actix_web::App;
+
+    fn in_flight_gauge() -> IntGaugeVec {
+        IntGaugeVec::new(prometheus::Opts::new("in_flight", "test"), &["dependency"]).unwrap()
+    }
+
+    fn checks(enabled: Vec<Check>) -> web::Data<HealthChecks> {
+        let config = Config {
+            enabled_checks: enabled,
+            health_history_size: 2,
+            ..Config::default()
+        };
+        web::Data::new(checks_with(config))
+    }
+
+    /// `HealthChecks` for `config`, with no Vault token.
+    fn checks_with(config: Config) -> HealthChecks {
+        HealthChecks::new(
+            web::Data::new(config),
+            web::Data::new(vault::VaultToken::new(None, None)),
+            reqwest::Client::new(),
+            &in_flight_gauge(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_check_names_round_trip() {
+        for check in Check::ALL {
+            assert_eq!(check.name().parse::<Check>(), Ok(check));
+        }
+        assert_eq!("VAULT".parse::<Check>(), Ok(Check::Vault));
+        assert!("redis".parse::<Check>().is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_http_check_sends_headers_and_expects_status() {
+        async fn guarded(req: actix_web::HttpRequest) -> HttpResponse {
+            match req.headers().get("authorization") {
+                Some(v) if v == "Bearer xyz" => HttpResponse::NoContent().finish(),
+                _ => HttpResponse::Unauthorized().finish(),
+            }
+        }
+        let server =
+            actix_web::HttpServer::new(|| App::new().route("/health", web::get().to(guarded)))
+                .workers(1)
+                .bind("127.0.0.1:0")
+                .unwrap();
+        let addr = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        let url = format!("http://{}/health", addr);
+        let config = Config {
+            http_checks: vec![
+                format!("authed={}|Authorization:Bearer xyz|expect=204", url)
+                    .parse()
+                    .unwrap(),
+                format!("anonymous={}|expect=401", url).parse().unwrap(),
+            ],
+            ..Config::default()
+        };
+        let result = check_http(&reqwest::Client::new(), &config).await;
+        assert!(result.is_healthy(), "{:?}", result);
+
+        let config = Config {
+            http_checks: vec![format!("authed={}", url).parse().unwrap()],
+            ..Config::default()
+        };
+        let result = check_http(&reqwest::Client::new(), &config).await;
+        assert_eq!(result.status, Status::Unhealthy);
+        assert_eq!(result.details["endpoints"][0]["http_status"], 401);
+        handle.stop(false).await;
+    }
+
+    #[actix_web::test]
+    async fn test_grpc_unconfigured_is_unhealthy() {
+        let app =
+            test::init_service(App::new().app_data(checks(vec![])).service(health_grpc)).await;
+        let req = test::TestRequest::get().uri("/health/grpc").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 503);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "GRPC_HEALTH_ADDR not set");
+    }
+
+    #[actix_web::test]
+    async fn test_disabled_checks_are_omitted() {
+        let app = test::init_service(
+            App::new()
+                .app_data(checks(vec![Check::TlsExpiry]))
+                .service(health_all)
+                .service(health_ready),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/health/all").to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["schema_version"], HEALTH_SCHEMA_VERSION);
+        assert_eq!(body["status"], "healthy");
+        assert!(body["services"].get("vault").is_none());
+        assert_eq!(body["services"]["tls_expiry"]["status"], "healthy");
+
+        // With vault disabled no critical check remains to fail readiness.
+        let req = test::TestRequest::get().uri("/health/ready").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["status"], "ready");
+        assert_eq!(body["checks"], serde_json::json!({}));
+        assert!(body.get("memory").is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_readiness_tolerates_failures_up_to_threshold() {
+        for (threshold, expected) in [(0, 503), (1, 200)] {
+            let config = Config {
+                enabled_checks: vec![Check::Vault],
+                vault_addr: "http://127.0.0.1:1".to_string(),
+                readiness_failure_threshold: threshold,
+                ..Config::default()
+            };
+            let checks = web::Data::new(checks_with(config));
+            let app = test::init_service(App::new().app_data(checks).service(health_ready)).await;
+
+            let req = test::TestRequest::get().uri("/health/ready").to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), expected);
+            let body: Value = test::read_body_json(resp).await;
+            assert_eq!(body["down"], serde_json::json!(["vault"]));
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_check_one() {
+        let app = test::init_service(
+            App::new()
+                .app_data(checks(vec![]))
+                .service(health_check_one),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/health/check/tls_expiry?fresh=true")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["check"], "tls_expiry");
+        assert_eq!(body["result"]["status"], "healthy");
+        let timing = &body["timing"];
+        assert!(timing["total_ms"].as_f64().unwrap() >= timing["check_ms"].as_f64().unwrap());
+
+        let req = test::TestRequest::get()
+            .uri("/health/check/redis")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 404);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "unknown_check");
+    }
+
+    #[test]
+    fn test_check_target() {
+        let config = Config {
+            vault_addr: "http://vault:8200".to_string(),
+            grpc_health_addr: Some("https://grpc.dev.local".to_string()),
+            ..Config::default()
+        };
+        assert_eq!(
+            check_target(&config, Check::Vault).as_deref(),
+            Some("vault:8200")
+        );
+        assert_eq!(
+            check_target(&config, Check::Grpc).as_deref(),
+            Some("grpc.dev.local:443")
+        );
+        assert_eq!(check_target(&config, Check::TlsExpiry), None);
+    }
+
+    #[actix_web::test]
+    async fn test_vault_check_against_mock_server() {
+        use wiremock::matchers::{header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let healthy = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/sys/health"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&healthy)
+            .await;
+        Mock::given(path("/v1/sys/mounts"))
+            .and(header("x-vault-token", "hvs.good"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"data": {"secret/": {"type": "kv"}}})),
+            )
+            .mount(&healthy)
+            .await;
+        Mock::given(path("/v1/sys/mounts"))
+            .and(header("x-vault-token", "hvs.garbled"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<html>oops"))
+            .mount(&healthy)
+            .await;
+        let sealed = MockServer::start().await;
+        Mock::given(path("/v1/sys/health"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&sealed)
+            .await;
+        let hung = MockServer::start().await;
+        Mock::given(path("/v1/sys/health"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(2)))
+            .mount(&hung)
+            .await;
+
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+        let check = |server: &MockServer, token: Option<&str>| {
+            let config = Config {
+                vault_addr: server.uri(),
+                vault_kv_mount: "secret".to_string(),
+                vault_max_response_bytes: 4096,
+                ..Config::default()
+            };
+            let http = http.clone();
+            let token = vault::VaultToken::new(token.map(String::from), None);
+            async move { check_vault(&http, &config, &token).await }
+        };
+
+        let result = check(&healthy, Some("hvs.good")).await;
+        assert!(result.is_healthy(), "{:?}", result);
+        assert_eq!(result.details["mount"], "secret");
+
+        let result = check(&healthy, Some("hvs.garbled")).await;
+        assert_eq!(result.status, Status::Unhealthy);
+        let error = result.details["error"].as_str().unwrap();
+        assert!(
+            error.starts_with("invalid Vault mounts response"),
+            "{}",
+            error
+        );
+
+        let result = check(&sealed, None).await;
+        assert_eq!(result.details["error"], "Vault is sealed");
+
+        let result = check(&hung, None).await;
+        assert_eq!(result.details["error"], "Vault unavailable");
+    }
+
+    #[actix_web::test]
+    async fn test_timeout_policy() {
+        use wiremock::matchers::path;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let hung = MockServer::start().await;
+        Mock::given(path("/v1/sys/health"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(2)))
+            .mount(&hung)
+            .await;
+
+        let responsive = MockServer::start().await;
+        Mock::given(path("/v1/sys/health"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&responsive)
+            .await;
+
+        for (policy, status, http_status) in [
+            (None, "unhealthy", 503),
+            (Some(TimeoutPolicy::Degraded), "degraded", 200),
+        ] {
+            let config = Config {
+                enabled_checks: vec![Check::Vault],
+                vault_addr: hung.uri(),
+                check_timeout_ms: 100,
+                check_timeout_policy: policy.map(|p| (Check::Vault, p)).into_iter().collect(),
+                ..Config::default()
+            };
+            let checks = web::Data::new(checks_with(config));
+            let app = test::init_service(
+                App::new()
+                    .app_data(checks.clone())
+                    .service(health_vault)
+                    .service(health_ready),
+            )
+            .await;
+
+            let req = test::TestRequest::get().uri("/health/vault").to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), http_status);
+            let body: Value = test::read_body_json(resp).await;
+            assert_eq!(body["status"], status);
+            assert_eq!(body["error"], "timed out after 100ms");
+            assert_eq!(body["timeout_policy"], status);
+
+            // Vault is critical, so even a degraded timeout fails readiness.
+            let req = test::TestRequest::get().uri("/health/ready").to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 503);
+
+            // The policy is reported when the check answers in time, too.
+            let config = Config {
+                enabled_checks: vec![Check::Vault],
+                vault_addr: responsive.uri(),
+                check_timeout_ms: 100,
+                check_timeout_policy: policy.map(|p| (Check::Vault, p)).into_iter().collect(),
+                ..Config::default()
+            };
+            let result = checks_with(config).run_fresh(Check::Vault).await;
+            assert!(result.is_healthy());
+            assert_eq!(result.details["timeout_policy"], status);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_vault_health_codes_are_mapped() {
+        use serde_json::json;
+        use wiremock::matchers::{path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let config = Config {
+            vault_addr: server.uri(),
+            ..Config::default()
+        };
+        let token = vault::VaultToken::new(None, None);
+        let check = |response: ResponseTemplate| {
+            let (server, config, token) = (&server, &config, &token);
+            async move {
+                server.reset().await;
+                Mock::given(path("/v1/sys/health"))
+                    .and(query_param("standbyok", "true"))
+                    .and(query_param("perfstandbyok", "true"))
+                    .respond_with(response)
+                    .expect(1)
+                    .mount(server)
+                    .await;
+                let result = check_vault(&reqwest::Client::new(), config, token).await;
+                server.verify().await;
+                result
+            }
+        };
+
+        // Every serving node answers 200; the body tells standbys apart.
+        for (body, role) in [
+            (json!({"sealed": false, "standby": false}), None),
+            (json!({"sealed": false, "standby": true}), Some("standby")),
+            (
+                json!({"sealed": false, "standby": true, "performance_standby": true}),
+                Some("performance_standby"),
+            ),
+        ] {
+            let result = check(ResponseTemplate::new(200).set_body_json(&body)).await;
+            assert!(result.is_healthy(), "{}", body);
+            assert_eq!(
+                result.details.get("node_state"),
+                role.map(Value::from).as_ref()
+            );
+        }
+
+        for (status, healthy, detail) in [
+            (429, true, ("node_state", "standby")),
+            (503, false, ("error", "Vault is sealed")),
+            (501, false, ("error", "Vault is not initialized")),
+            (500, false, ("error", "Vault unavailable")),
+        ] {
+            let result = check(ResponseTemplate::new(status)).await;
+            assert_eq!(result.is_healthy(), healthy, "HTTP {}", status);
+            assert_eq!(result.details[detail.0], detail.1, "HTTP {}", status);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_checks_accept_ipv6_literals() {
+        async fn vault_health() -> HttpResponse {
+            HttpResponse::Ok().json(serde_json::json!({"sealed": false, "standby": false}))
+        }
+        let server = actix_web::HttpServer::new(|| {
+            App::new()
+                .route("/v1/sys/health", web::get().to(vault_health))
+                .route("/health", web::get().to(HttpResponse::Ok))
+        })
+        .workers(1)
+        .bind("[::1]:0")
+        .unwrap();
+        let addr = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        let base = format!("http://[::1]:{}", addr.port());
+        let config = Config {
+            vault_addr: base.clone(),
+            vault_nodes: vec![base.clone()],
+            grpc_health_addr: Some("http://[fe80::1]:50051".to_string()),
+            http_checks: vec![format!("api={}/health", base).parse().unwrap()],
+            ..Config::default()
+        };
+        let target = format!("[::1]:{}", addr.port());
+        assert_eq!(check_target(&config, Check::Vault), Some(target.clone()));
+        assert_eq!(check_target(&config, Check::Http), Some(target.clone()));
+        assert_eq!(
+            check_target(&config, Check::Grpc).as_deref(),
+            Some("[fe80::1]:50051")
+        );
+        let timing = connect_timing(&target).await;
+        assert_eq!(timing.get("error"), None, "{:?}", timing);
+
+        let http = reqwest::Client::new();
+        let token = vault::VaultToken::new(None, None);
+        assert!(check_vault(&http, &config, &token).await.is_healthy());
+        let result = check_http(&http, &config).await;
+        assert!(result.is_healthy(), "{:?}", result);
+        assert_eq!(
+            result.details["endpoints"][0]["url"],
+            format!("{}/health", base)
+        );
+        let node = vault::probe_node(&http, &base, 4096).await;
+        assert_eq!(node.addr, base);
+        assert_eq!((node.reachable, node.active), (true, Some(true)));
+
+        let tls = tls_expiry::check_host("[::1]:1", 30).await;
+        assert!(tls.error.unwrap().starts_with("connect failed"));
+        assert_eq!(tls.host, "[::1]:1");
+        handle.stop(false).await;
+    }
+
+    #[test]
+    fn test_readiness_warms_up_before_not_ready() {
+        let gauge = in_flight_gauge();
+        let checks = checks_with(Config::default()).with_readiness_gauge(gauge.clone());
+        let state = |name| gauge.with_label_values(&[name]).get();
+        assert_eq!(state("warming"), 1);
+
+        assert_eq!(checks.readiness_state(false, false), Readiness::Warming);
+        assert_eq!(checks.readiness_state(true, false), Readiness::Ready);
+        assert_eq!((state("warming"), state("ready")), (0, 1));
+        assert_eq!(checks.readiness_state(false, false), Readiness::NotReady);
+        assert_eq!((state("ready"), state("not_ready")), (0, 1));
+
+        let fresh = super::tests::checks(vec![]);
+        assert_eq!(fresh.readiness_state(false, true), Readiness::NotReady);
+    }
+
+    #[test]
+    fn test_readiness_flips_only_after_consecutive_results() {
+        let config = Config {
+            readiness_failure_count: 3,
+            readiness_success_count: 2,
+            ..Config::default()
+        };
+        let checks = checks_with(config);
+        let states: Vec<&str> = [
+            true, true, // warming -> ready on the second pass
+            false, false, true, // a blip that recovers before three failures
+            false, false, false, // -> not_ready
+            true, false, true, true, // an interrupted run doesn't count
+        ]
+        .into_iter()
+        .map(|passed| checks.readiness_state(passed, false).name())
+        .collect();
+        assert_eq!(
+            states,
+            [
+                "warming",
+                "ready",
+                "ready",
+                "ready",
+                "ready",
+                "ready",
+                "ready",
+                "not_ready",
+                "not_ready",
+                "not_ready",
+                "not_ready",
+                "ready",
+            ]
+        );
+        assert_eq!(checks.readiness_state(true, true), Readiness::NotReady);
+    }
+
+    #[actix_web::test]
+    async fn test_not_ready_while_draining() {
+        let draining = web::Data::new(drain::Draining::default());
+        let app = test::init_service(
+            App::new()
+                .app_data(checks(vec![Check::TlsExpiry]))
+                .app_data(draining.clone())
+                .service(health_ready),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/health/ready").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+        draining.start();
+        let req = test::TestRequest::get().uri("/health/ready").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 503);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["draining"], true);
+        assert!(body.get("checks").is_none());
+    }
+
+    #[test]
+    fn test_dependency_order_rejects_cycles() {
+        let order = dependency_order(&[(Check::Vault, Check::Grpc)]).unwrap();
+        let position = |check| order.iter().position(|c| *c == check).unwrap();
+        assert!(position(Check::Grpc) < position(Check::Vault));
+
+        let err = dependency_order(&[(Check::Vault, Check::Grpc), (Check::Grpc, Check::Vault)])
+            .unwrap_err();
+        assert!(err.contains("cycle"));
+        assert!(err.contains("vault") && err.contains("grpc"));
+    }
+
+    #[actix_web::test]
+    async fn test_failed_prerequisite_skips_dependent() {
+        // gRPC is unconfigured, so it fails; Vault depends on it.
+        let config = Config {
+            enabled_checks: vec![Check::Vault, Check::Grpc],
+            check_dependencies: vec![(Check::Vault, Check::Grpc)],
+            ..Config::default()
+        };
+        let checks = web::Data::new(checks_with(config));
+        let app = test::init_service(App::new().app_data(checks).service(health_ready)).await;
+
+        let req = test::TestRequest::get().uri("/health/ready").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 503);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["checks"]["vault"], "skipped: depends on grpc");
+        assert_eq!(body["checks"]["grpc"], "unhealthy");
+    }
+
+    #[actix_web::test]
+    async fn test_vault_unwrap_round_trip() {
+        use wiremock::matchers::{header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        // A Vault that wraps secret/data/app and unwraps it, once per check.
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/secret/data/app"))
+            .and(header("x-vault-wrap-ttl", "60s"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"wrap_info": {"token": "hvs.wrapped"}})),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/sys/wrapping/unwrap"))
+            .and(header("x-vault-token", "hvs.wrapped"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": {"data": {"username": "app", "password": "s3cret"}, "metadata": {}}
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        for (expected, healthy) in [
+            (vec!["username", "password"], true),
+            (vec!["api_key"], false),
+        ] {
+            let config = Config {
+                vault_addr: server.uri(),
+                vault_unwrap_check_path: Some("secret/data/app".to_string()),
+                vault_unwrap_expected_keys: expected.iter().map(|k| k.to_string()).collect(),
+                vault_max_response_bytes: 4096,
+                ..Config::default()
+            };
+            let result = check_vault_unwrap(
+                &reqwest::Client::new(),
+                &config,
+                "hvs.app",
+                "secret/data/app",
+                CheckResult::new(Status::Healthy),
+            )
+            .await;
+            assert_eq!(result.is_healthy(), healthy);
+            let unwrap = &result.details["unwrap"];
+            assert_eq!(unwrap["keys"]["password"], "<redacted>");
+            assert!(!serde_json::to_string(&result).unwrap().contains("s3cret"));
+            if !healthy {
+                assert_eq!(unwrap["missing_keys"][0], "api_key");
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_full_bulkhead_reports_saturated() {
+        let config = Config {
+            check_max_concurrency: HashMap::from([(Check::Grpc, 1)]),
+            ..Config::default()
+        };
+        let checks = checks_with(config);
+
+        let held = checks.bulkheads[&Check::Grpc]
+            .permits
+            .try_acquire()
+            .unwrap();
+        let result = checks.run(Check::Grpc).await;
+        assert_eq!(result.status, Status::Saturated);
+        assert_eq!(result.details["max_concurrency"], 1);
+        drop(held);
+
+        assert_eq!(checks.run(Check::Grpc).await.status, Status::Unhealthy);
+        assert_eq!(checks.bulkheads[&Check::Grpc].in_flight.get(), 0);
+    }
+
+    #[actix_web::test]
+    async fn test_concurrent_runs_share_one_probe() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::AsyncWriteExt;
+
+        // A Vault that answers every connection slowly with a 503.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let counted = connections.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                counted.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    let _ = stream
+                        .write_all(b"HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                        .await;
+                });
+            }
+        });
+
+        let config = Config {
+            vault_addr: format!("http://{}", addr),
+            ..Config::default()
+        };
+        let checks = checks_with(config);
+
+        let results =
+            futures_util::future::join_all((0..5).map(|_| checks.run(Check::Vault))).await;
+        assert!(results.iter().all(|r| r.status == Status::Unhealthy));
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+        assert!(checks.probes.lock().unwrap().is_empty());
+
+        checks.run(Check::Vault).await;
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+
+        // An on-demand check probes afresh even with a probe in flight.
+        futures_util::future::join(checks.run(Check::Vault), checks.run_fresh(Check::Vault)).await;
+        assert_eq!(connections.load(Ordering::SeqCst), 4);
+    }
+
+    #[actix_web::test]
+    async fn test_history_is_bounded_and_newest_first() {
+        let checks = checks(vec![Check::TlsExpiry]);
+        for timestamp in ["first", "second", "third"] {
+            checks.record(HistoryEntry {
+                timestamp: timestamp.to_string(),
+                status: "healthy",
+                services: Map::new(),
+            });
+        }
+        let app = test::init_service(App::new().app_data(checks).service(health_history)).await;
+
+        let req = test::TestRequest::get().uri("/health/history").to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        let timestamps: Vec<&str> = body["history"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["timestamp"].as_str().unwrap())
+            .collect();
+        assert_eq!(timestamps, ["third", "second"]);
+    }
+
+    #[actix_web::test]
+    async fn test_ready_sheds_under_memory_pressure() {
+        if memory::rss_bytes().is_none() {
+            return;
+        }
+        let config = Config {
+            memory_ready_limit_mb: Some(1),
+            ..Config::default()
+        };
+        let checks = web::Data::new(checks_with(config));
+        let app = test::init_service(App::new().app_data(checks).service(health_ready)).await;
+
+        let req = test::TestRequest::get().uri("/health/ready").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 503);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["status"], "warming");
+        assert_eq!(body["memory"]["status"], "unhealthy");
+        assert_eq!(body["memory"]["limit_bytes"], 1024 * 1024);
+        assert!(body["memory"]["current_bytes"].as_u64().unwrap() > 1024 * 1024);
+
+        // A limit too large to express in bytes is no limit at all.
+        let config = Config {
+            memory_ready_limit_mb: Some(u64::MAX),
+            ..Config::default()
+        };
+        let result = memory_pressure(&config).unwrap();
+        assert_eq!(result.status, Status::Healthy);
+        assert_eq!(result.details["limit_bytes"], u64::MAX);
+    }
+}
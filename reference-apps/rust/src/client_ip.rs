@@ -0,0 +1,187 @@
+//! Client IP resolution behind reverse proxies.
+//!
+//! With `TRUST_PROXY=true` the client address is taken from
+//! `X-Forwarded-For`, but only hops appended by proxies listed in
+//! `TRUSTED_PROXIES` are believed. The chain is walked right-to-left and the
+//! first address that is not a trusted proxy is the client; anything to the
+//! left of it was supplied by the client and may be spoofed.
+
+use std::net::IpAddr;
+
+use actix_web::dev::ServiceRequest;
+
+use crate::config::Config;
+
+/// An IPv4 or IPv6 network in CIDR notation. A bare address is a /32 or /128.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn parse(s: &str) -> Option<Cidr> {
+        let (addr, prefix) = match s.trim().split_once('/') {
+            Some((addr, prefix)) => (
+                addr.parse::<IpAddr>().ok()?,
+                Some(prefix.parse::<u8>().ok()?),
+            ),
+            None => (s.trim().parse::<IpAddr>().ok()?, None),
+        };
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+        (prefix <= max).then_some(Cidr { addr, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Resolves the client address from the socket peer and `X-Forwarded-For`.
+///
+/// Malformed chain entries are skipped. If every hop is a trusted proxy the
+/// left-most valid address is used.
+pub fn resolve(
+    peer: Option<IpAddr>,
+    forwarded_for: Option<&str>,
+    trust_proxy: bool,
+    trusted_proxies: &[Cidr],
+) -> Option<IpAddr> {
+    let peer = peer.map(|ip| ip.to_canonical());
+    if !trust_proxy {
+        return peer;
+    }
+
+    let is_trusted = |ip: IpAddr| trusted_proxies.iter().any(|cidr| cidr.contains(ip));
+    if let Some(ip) = peer {
+        if !is_trusted(ip) {
+            return Some(ip);
+        }
+    }
+
+    let hops: Vec<IpAddr> = forwarded_for
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|hop| hop.trim().parse::<IpAddr>().ok())
+        .map(|ip| ip.to_canonical())
+        .collect();
+
+    hops.iter()
+        .rev()
+        .find(|ip| !is_trusted(**ip))
+        .or(hops.first())
+        .copied()
+        .or(peer)
+}
+
+/// Resolves the client address of an incoming request.
+pub fn from_request(req: &ServiceRequest, config: &Config) -> Option<IpAddr> {
+    let forwarded_for = req
+        .headers()
+        .get("X-Forwarded-For")
+        .and_then(|v| v.to_str().ok());
+    resolve(
+        req.peer_addr().map(|addr| addr.ip()),
+        forwarded_for,
+        config.trust_proxy,
+        &config.trusted_proxies,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn proxies() -> Vec<Cidr> {
+        vec![
+            Cidr::parse("10.0.0.0/8").unwrap(),
+            Cidr::parse("172.20.0.5").unwrap(),
+        ]
+    }
+
+    #[test]
+    fn test_cidr_parse_and_contains() {
+        let net = Cidr::parse("10.1.0.0/16").unwrap();
+        assert!(net.contains(ip("10.1.2.3")));
+        assert!(!net.contains(ip("10.2.0.1")));
+        assert!(net.contains(ip("::ffff:10.1.0.1")));
+        assert!(Cidr::parse("fd00::/8").unwrap().contains(ip("fd12::1")));
+        assert!(Cidr::parse("0.0.0.0/0").unwrap().contains(ip("8.8.8.8")));
+        assert_eq!(Cidr::parse("10.0.0.0/33"), None);
+        assert_eq!(Cidr::parse("not-an-ip"), None);
+    }
+
+    #[test]
+    fn test_untrusted_mode_uses_peer() {
+        let client = resolve(Some(ip("10.0.0.1")), Some("1.2.3.4"), false, &proxies());
+        assert_eq!(client, Some(ip("10.0.0.1")));
+    }
+
+    #[test]
+    fn test_legitimate_chain() {
+        let client = resolve(
+            Some(ip("10.0.0.1")),
+            Some("203.0.113.7, 172.20.0.5"),
+            true,
+            &proxies(),
+        );
+        assert_eq!(client, Some(ip("203.0.113.7")));
+    }
+
+    #[test]
+    fn test_spoofed_chain() {
+        // The client prepended a fake address; the proxy appended the real one.
+        let client = resolve(
+            Some(ip("10.0.0.1")),
+            Some("1.1.1.1, 198.51.100.9"),
+            true,
+            &proxies(),
+        );
+        assert_eq!(client, Some(ip("198.51.100.9")));
+    }
+
+    #[test]
+    fn test_header_ignored_from_untrusted_peer() {
+        let client = resolve(Some(ip("198.51.100.9")), Some("1.1.1.1"), true, &proxies());
+        assert_eq!(client, Some(ip("198.51.100.9")));
+    }
+
+    #[test]
+    fn test_malformed_entries_are_skipped() {
+        let client = resolve(
+            Some(ip("10.0.0.1")),
+            Some("203.0.113.7, not-an-ip, , 10.2.2.2"),
+            true,
+            &proxies(),
+        );
+        assert_eq!(client, Some(ip("203.0.113.7")));
+    }
+
+    #[test]
+    fn test_all_trusted_uses_leftmost() {
+        let client = resolve(
+            Some(ip("10.0.0.1")),
+            Some("10.9.9.9, 10.8.8.8"),
+            true,
+            &proxies(),
+        );
+        assert_eq!(client, Some(ip("10.9.9.9")));
+        let client = resolve(Some(ip("10.0.0.1")), None, true, &proxies());
+        assert_eq!(client, Some(ip("10.0.0.1")));
+    }
+}
@@ -0,0 +1,929 @@
+//! Runtime configuration loaded from environment variables at startup.
+//!
+//! [`Config`] is fixed for the process lifetime. [`HotConfig`] is the subset
+//! that can be changed on a running process: it is re-read on SIGHUP, with
+//! `KEY=VALUE` lines in the file named by `CONFIG_FILE` overriding the
+//! process environment.
+//!
+//! `APP_ENV` picks a [`Profile`], which supplies the defaults for settings
+//! that should differ between dev and prod; their own variables still win.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use crate::checks::{Check, TimeoutPolicy};
+use crate::client_ip::Cidr;
+use crate::cors::CorsPolicy;
+use crate::http_check::HttpCheck;
+use crate::http_client::{ProxyConfig, VaultTls};
+use crate::instance;
+use crate::json_case::FieldCase;
+use crate::registry::RegistryKind;
+use crate::routes;
+use crate::timestamp::TimestampTz;
+use crate::trailing_slash::TrailingSlashMode;
+use crate::vault::RequiredSecret;
+
+/// Deployment environment (`APP_ENV`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Profile {
+    #[default]
+    Dev,
+    Staging,
+    Prod,
+}
+
+impl Profile {
+    pub fn name(self) -> &'static str {
+        match self {
+            Profile::Dev => "dev",
+            Profile::Staging => "staging",
+            Profile::Prod => "prod",
+        }
+    }
+
+    /// Any origin may call the API; otherwise only `CORS_ALLOWED_ORIGINS`.
+    fn permissive_cors(self) -> bool {
+        self == Profile::Dev
+    }
+
+    /// `/debug/*` routes are registered.
+    fn debug_endpoints(self) -> bool {
+        self != Profile::Prod
+    }
+
+    /// Variables that must be set rather than defaulted; each entry is
+    /// satisfied by any one of its names.
+    fn required_vars(self) -> &'static [&'static [&'static str]] {
+        match self {
+            Profile::Dev => &[],
+            Profile::Staging => &[&["VAULT_ADDR"], &["VAULT_TOKEN", "VAULT_TOKEN_FILE"]],
+            Profile::Prod => &[
+                &["VAULT_ADDR"],
+                &["VAULT_TOKEN", "VAULT_TOKEN_FILE"],
+                &["VAULT_KV_MOUNT"],
+                &["CORS_ALLOWED_ORIGINS"],
+            ],
+        }
+    }
+}
+
+/// The profile's required variables that `lookup` finds unset or empty, as
+/// `A` or `A or B`.
+fn missing_required(profile: Profile, lookup: impl Fn(&str) -> Option<String>) -> Vec<String> {
+    profile
+        .required_vars()
+        .iter()
+        .filter(|names| {
+            !names
+                .iter()
+                .any(|name| lookup(name).is_some_and(|v| !v.trim().is_empty()))
+        })
+        .map(|names| names.join(" or "))
+        .collect()
+}
+
+impl std::str::FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "dev" | "development" => Ok(Profile::Dev),
+            "staging" | "stage" => Ok(Profile::Staging),
+            "prod" | "production" => Ok(Profile::Prod),
+            other => Err(format!("unknown profile '{}'", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Deployment profile supplying environment-specific defaults (`APP_ENV`).
+    pub profile: Profile,
+    /// This process's ID, sent as `X-Served-By` (`INSTANCE_ID`, else generated).
+    pub instance_id: String,
+    /// Allow any origin, method and header (`CORS_PERMISSIVE`).
+    pub cors_permissive: bool,
+    /// Origins allowed when CORS isn't permissive (`CORS_ALLOWED_ORIGINS`).
+    pub cors_allowed_origins: Vec<String>,
+    /// Per-path-prefix CORS policies (`CORS_POLICIES`).
+    pub cors_policies: Vec<CorsPolicy>,
+    /// Register the `/debug/*` routes (`DEBUG_ENDPOINTS`).
+    pub debug_endpoints: bool,
+    /// Allow faults to be injected through `/debug/chaos` (`CHAOS_ENABLED`).
+    pub chaos_enabled: bool,
+    /// Initial runtime feature flags (`FEATURE_FLAGS`).
+    pub feature_flags: HashMap<String, bool>,
+    /// Refuse to start while the profile's required variables are unset
+    /// (`STRICT_CONFIG`).
+    pub strict_config: bool,
+    /// Vault address (`VAULT_ADDR`).
+    pub vault_addr: String,
+    /// KV mount verified by the Vault health check (`VAULT_KV_MOUNT`).
+    pub vault_kv_mount: String,
+    /// Individual HA cluster members probed by `/health/vault` (`VAULT_NODES`).
+    pub vault_nodes: Vec<String>,
+    /// Vault Agent caching sidecar checked by `/health/vault` (`VAULT_AGENT_ADDR`).
+    pub vault_agent_addr: Option<String>,
+    /// Also check upstream Vault when an agent is configured
+    /// (`VAULT_AGENT_CHECK_UPSTREAM`).
+    pub vault_agent_check_upstream: bool,
+    /// File to read the Vault token from when `VAULT_TOKEN` is unset (`VAULT_TOKEN_FILE`).
+    pub vault_token_file: Option<PathBuf>,
+    /// How often the token file is checked for changes (`VAULT_TOKEN_FILE_POLL_SECS`).
+    pub vault_token_file_poll_secs: u64,
+    /// Largest Vault response body the app will read (`VAULT_MAX_RESPONSE_BYTES`).
+    pub vault_max_response_bytes: usize,
+    /// Path whose token capabilities `/health/vault` verifies
+    /// (`VAULT_CAPABILITY_CHECK_PATH`).
+    pub vault_capability_check_path: Option<String>,
+    /// Capabilities the token must hold on that path (`VAULT_REQUIRED_CAPABILITIES`).
+    pub vault_required_capabilities: Vec<String>,
+    /// Secret `/health/vault` reads response-wrapped and unwraps
+    /// (`VAULT_UNWRAP_CHECK_PATH`).
+    pub vault_unwrap_check_path: Option<String>,
+    /// Keys the unwrapped secret must contain (`VAULT_UNWRAP_EXPECTED_KEYS`).
+    pub vault_unwrap_expected_keys: Vec<String>,
+    /// Secrets read at startup, each with the keys it must hold
+    /// (`REQUIRED_SECRETS`).
+    pub required_secrets: Vec<RequiredSecret>,
+    /// Refuse to start while a required secret or key is missing
+    /// (`STRICT_SECRETS`).
+    pub strict_secrets: bool,
+    /// Deadline for each of those reads (`REQUIRED_SECRETS_TIMEOUT_MS`).
+    pub required_secrets_timeout_ms: u64,
+    /// Derive client addresses from `X-Forwarded-For` (`TRUST_PROXY`).
+    pub trust_proxy: bool,
+    /// Proxy networks whose `X-Forwarded-For` hops are believed (`TRUSTED_PROXIES`).
+    pub trusted_proxies: Vec<Cidr>,
+    /// `host:port` targets whose certificates are checked (`TLS_CHECK_HOSTS`).
+    pub tls_check_hosts: Vec<String>,
+    /// Certificates expiring within this many days are unhealthy (`TLS_EXPIRY_WARN_DAYS`).
+    pub tls_expiry_warn_days: i64,
+    /// File persisting the restart count across boots (`RESTART_COUNT_FILE`).
+    pub restart_count_file: Option<PathBuf>,
+    /// Accepted `Host` values; empty disables the check (`ALLOWED_HOSTS`).
+    pub allowed_hosts: Vec<String>,
+    /// Path prefixes that skip the Host check (`HOST_CHECK_EXEMPT_PATHS`).
+    pub host_check_exempt_paths: Vec<String>,
+    /// Reload [`HotConfig`] on Postgres notifications (`ENABLE_PG_CONFIG_NOTIFY`).
+    pub pg_config_notify: bool,
+    /// Channel to `LISTEN` on (`CONFIG_NOTIFY_CHANNEL`).
+    pub config_notify_channel: String,
+    /// How long shutdown waits for each dependency connection to close
+    /// (`DEPENDENCY_CLOSE_TIMEOUT_SECS`).
+    pub dependency_close_timeout_secs: u64,
+    /// How long SIGTERM reports not-ready before shutdown starts
+    /// (`PRE_STOP_DELAY_SECS`).
+    pub pre_stop_delay_secs: u64,
+    /// Disable Nagle's algorithm on accepted connections (`TCP_NODELAY`).
+    pub tcp_nodelay: bool,
+    /// Set `SO_REUSEADDR` on the listening socket (`SO_REUSEADDR`).
+    pub so_reuseaddr: bool,
+    pub postgres: PostgresConfig,
+    /// Outbound proxies (`OUTBOUND_PROXY`, `HTTP_PROXY`, `HTTPS_PROXY`, `NO_PROXY`).
+    pub proxy: ProxyConfig,
+    /// Client certificate and CA for Vault calls (`VAULT_CLIENT_CERT`,
+    /// `VAULT_CLIENT_KEY`, `VAULT_CA_CERT`).
+    pub vault_tls: VaultTls,
+    /// Default per-request timeout (`REQUEST_TIMEOUT_MS`).
+    pub request_timeout_ms: u64,
+    /// Upper bound for a caller-supplied `X-Request-Timeout` (`REQUEST_TIMEOUT_MAX_MS`).
+    pub request_timeout_max_ms: u64,
+    /// How requests with a trailing slash are handled (`TRAILING_SLASH_MODE`).
+    pub trailing_slash_mode: TrailingSlashMode,
+    /// Path prefix a gateway strips before forwarding to us (`ROUTE_PREFIX`),
+    /// normalized to `/prefix` or empty. Generated `Location`s include it.
+    pub route_prefix: String,
+    /// Key naming in JSON responses (`JSON_FIELD_CASE`).
+    pub json_field_case: FieldCase,
+    /// Pretty-print JSON responses unless `?pretty=false` (`JSON_PRETTY_DEFAULT`).
+    pub json_pretty_default: bool,
+    /// Zone for emitted timestamps (`TIMESTAMP_TZ`).
+    pub timestamp_tz: TimestampTz,
+    /// Checks registered for `/health/all` and readiness (`HEALTH_ENABLED_CHECKS`,
+    /// `HEALTH_<NAME>_ENABLED`).
+    pub enabled_checks: Vec<Check>,
+    /// How many `/health/all` results `/health/history` keeps (`HEALTH_HISTORY_SIZE`).
+    pub health_history_size: usize,
+    /// Per-check bulkhead sizes from `<NAME>_MAX_CONCURRENCY`; checks not
+    /// listed use [`crate::checks::DEFAULT_MAX_CONCURRENCY`].
+    pub check_max_concurrency: HashMap<Check, usize>,
+    /// Deadline for each check run, 0 for none (`HEALTH_CHECK_TIMEOUT_MS`).
+    pub check_timeout_ms: u64,
+    /// Per-check `HEALTH_<NAME>_TIMEOUT_POLICY`; checks not listed are
+    /// unhealthy on timeout.
+    pub check_timeout_policy: HashMap<Check, TimeoutPolicy>,
+    /// `(dependent, prerequisite)` pairs from `HEALTH_CHECK_DEPENDENCIES`.
+    pub check_dependencies: Vec<(Check, Check)>,
+    /// Generic HTTP endpoints probed by the `http` check (`HTTP_CHECKS`).
+    pub http_checks: Vec<HttpCheck>,
+    /// Downstream gRPC server probed by `/health/grpc` (`GRPC_HEALTH_ADDR`).
+    pub grpc_health_addr: Option<String>,
+    /// Service name sent in the gRPC health `Check` (`GRPC_HEALTH_SERVICE`);
+    /// empty asks about the server as a whole.
+    pub grpc_health_service: String,
+    /// Serve the gRPC health protocol (`ENABLE_GRPC_HEALTH`).
+    pub enable_grpc_health: bool,
+    /// Port for the gRPC health server (`GRPC_PORT`).
+    pub grpc_port: u16,
+    /// How often the gRPC health server re-evaluates readiness
+    /// (`GRPC_HEALTH_INTERVAL_SECS`).
+    pub grpc_health_interval_secs: u64,
+    /// RSS above which `/health/ready` reports not ready (`MEMORY_READY_LIMIT_MB`).
+    pub memory_ready_limit_mb: Option<u64>,
+    /// Critical checks that may fail while still reporting ready
+    /// (`READINESS_FAILURE_THRESHOLD`).
+    pub readiness_failure_threshold: usize,
+    /// Consecutive failing readiness evaluations before reporting not ready
+    /// (`READINESS_FAILURE_COUNT`).
+    pub readiness_failure_count: u32,
+    /// Consecutive passing evaluations before reporting ready again
+    /// (`READINESS_SUCCESS_COUNT`).
+    pub readiness_success_count: u32,
+    /// StatsD/DogStatsD UDP endpoint metrics are mirrored to (`STATSD_ADDR`).
+    pub statsd_addr: Option<String>,
+    /// Prefix for StatsD metric names (`STATSD_PREFIX`).
+    pub statsd_prefix: String,
+    /// Fraction of requests sent to StatsD, 0.0 to 1.0 (`STATSD_SAMPLE_RATE`).
+    pub statsd_sample_rate: f64,
+    /// Pushgateway metrics are pushed to (`PUSHGATEWAY_URL`).
+    pub pushgateway_url: Option<String>,
+    /// `job` the pushed metrics are grouped under (`PUSHGATEWAY_JOB`).
+    pub pushgateway_job: String,
+    /// Extra grouping labels for pushes (`PUSHGATEWAY_GROUPING`).
+    pub pushgateway_grouping: Vec<(String, String)>,
+    /// Seconds between pushes (`PUSHGATEWAY_INTERVAL_SECS`).
+    pub pushgateway_interval_secs: u64,
+    /// Service registry heartbeats are sent to (`REGISTRY_URL`).
+    pub registry_url: Option<String>,
+    /// Registry protocol, `http` or `consul` (`REGISTRY_KIND`).
+    pub registry_kind: RegistryKind,
+    /// Service name registered under (`REGISTRY_SERVICE`).
+    pub registry_service: String,
+    /// Address advertised to the registry (`REGISTRY_ADDRESS`, else `HOSTNAME`).
+    pub registry_address: String,
+    /// Seconds between registry heartbeats (`HEARTBEAT_INTERVAL_SECS`).
+    pub heartbeat_interval_secs: u64,
+    /// Longest metric label value kept before truncation (`METRIC_LABEL_MAX_LEN`).
+    pub metric_label_max_len: usize,
+    /// Credential required to scrape `/metrics`, as a bearer token or basic
+    /// auth password (`METRICS_AUTH_TOKEN`).
+    pub metrics_auth_token: Option<Secret>,
+    /// Key for verifying HMAC-signed requests (`HMAC_SECRET`).
+    pub hmac_secret: Option<Secret>,
+    /// Largest allowed skew of a signed request's `X-Timestamp` (`HMAC_MAX_AGE_SECS`).
+    pub hmac_max_age_secs: u64,
+    /// Path prefixes that need no signature (`HMAC_EXEMPT_PATHS`).
+    pub hmac_exempt_paths: Vec<String>,
+    /// Largest request body accepted (`MAX_BODY_BYTES`).
+    pub max_body_bytes: usize,
+    /// Fraction of requests recorded, 0 for none (`RECORD_SAMPLE_RATE`).
+    pub record_sample_rate: f64,
+    /// JSONL file sampled requests are appended to (`RECORD_FILE`).
+    pub record_file: PathBuf,
+    /// Size at which the record file is rotated (`RECORD_MAX_BYTES`).
+    pub record_max_bytes: u64,
+    /// Most of each request body recorded (`RECORD_BODY_MAX_BYTES`).
+    pub record_body_max_bytes: usize,
+}
+
+/// A credential from the environment, kept out of `Debug` output.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(pub String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+/// Postgres connection settings (`POSTGRES_HOST`, `POSTGRES_PORT`,
+/// `POSTGRES_USER`, `POSTGRES_PASSWORD`, `POSTGRES_DB`).
+#[derive(Clone, Default)]
+pub struct PostgresConfig {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub password: String,
+    pub dbname: String,
+}
+
+impl std::fmt::Debug for PostgresConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PostgresConfig")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("user", &self.user)
+            .field("password", &"<redacted>")
+            .field("dbname", &self.dbname)
+            .finish()
+    }
+}
+
+impl Config {
+    /// Checks the profile's required variables are set. Missing ones fail
+    /// startup under `STRICT_CONFIG` and are only warned about otherwise.
+    pub fn check_required(&self) -> Result<(), String> {
+        let missing = missing_required(self.profile, |name| env::var(name).ok());
+        if missing.is_empty() {
+            return Ok(());
+        }
+        if self.strict_config {
+            return Err(format!(
+                "STRICT_CONFIG: required for the {} profile but unset: {}",
+                self.profile.name(),
+                missing.join(", ")
+            ));
+        }
+        for name in missing {
+            log::warn!(
+                "{} is unset; the {} profile expects it, using the default",
+                name,
+                self.profile.name()
+            );
+        }
+        Ok(())
+    }
+
+    pub fn from_env() -> Config {
+        Config::from_lookup(&|name| env::var(name).ok())
+    }
+
+    fn from_lookup(get: &dyn Fn(&str) -> Option<String>) -> Config {
+        let vars = Vars(get);
+        let profile = vars.parse("APP_ENV", Profile::Dev);
+        // Exempt by default from checks probes and scrapers can't satisfy.
+        let probe_paths = [routes::HEALTH_PREFIXES, &["/metrics"]].concat();
+        Config {
+            profile,
+            instance_id: vars
+                .any(&["INSTANCE_ID"])
+                .unwrap_or_else(instance::generate_id),
+            cors_permissive: vars.profile_bool(
+                "CORS_PERMISSIVE",
+                profile,
+                profile.permissive_cors(),
+            ),
+            cors_allowed_origins: vars.list("CORS_ALLOWED_ORIGINS"),
+            cors_policies: cors_policies(&vars),
+            debug_endpoints: vars.profile_bool(
+                "DEBUG_ENDPOINTS",
+                profile,
+                profile.debug_endpoints(),
+            ),
+            chaos_enabled: vars.bool("CHAOS_ENABLED", false),
+            feature_flags: feature_flags(&vars),
+            strict_config: vars.bool("STRICT_CONFIG", false),
+            vault_addr: vars
+                .get("VAULT_ADDR")
+                .unwrap_or_else(|| "http://vault:8200".to_string()),
+            vault_kv_mount: vars
+                .get("VAULT_KV_MOUNT")
+                .unwrap_or_else(|| "secret".to_string()),
+            vault_nodes: vars.list("VAULT_NODES"),
+            vault_agent_addr: vars.get("VAULT_AGENT_ADDR"),
+            vault_agent_check_upstream: vars.bool("VAULT_AGENT_CHECK_UPSTREAM", true),
+            vault_token_file: vars.get("VAULT_TOKEN_FILE").map(PathBuf::from),
+            vault_token_file_poll_secs: vars.parse("VAULT_TOKEN_FILE_POLL_SECS", 10),
+            vault_max_response_bytes: vars.parse("VAULT_MAX_RESPONSE_BYTES", 1024 * 1024),
+            vault_capability_check_path: vars.get("VAULT_CAPABILITY_CHECK_PATH"),
+            vault_required_capabilities: vars.list_or("VAULT_REQUIRED_CAPABILITIES", &["read"]),
+            vault_unwrap_check_path: vars.get("VAULT_UNWRAP_CHECK_PATH"),
+            vault_unwrap_expected_keys: vars.list("VAULT_UNWRAP_EXPECTED_KEYS"),
+            required_secrets: required_secrets(&vars),
+            strict_secrets: vars.bool("STRICT_SECRETS", false),
+            required_secrets_timeout_ms: vars.parse("REQUIRED_SECRETS_TIMEOUT_MS", 5000),
+            trust_proxy: vars.bool("TRUST_PROXY", false),
+            trusted_proxies: vars
+                .list("TRUSTED_PROXIES")
+                .iter()
+                .filter_map(|s| {
+                    let cidr = Cidr::parse(s);
+                    if cidr.is_none() {
+                        log::warn!("Ignoring invalid TRUSTED_PROXIES entry '{}'", s);
+                    }
+                    cidr
+                })
+                .collect(),
+            tls_check_hosts: vars.list("TLS_CHECK_HOSTS"),
+            tls_expiry_warn_days: vars.parse("TLS_EXPIRY_WARN_DAYS", 30),
+            restart_count_file: vars.get("RESTART_COUNT_FILE").map(PathBuf::from),
+            allowed_hosts: vars
+                .list("ALLOWED_HOSTS")
+                .into_iter()
+                .map(|h| h.to_ascii_lowercase())
+                .collect(),
+            host_check_exempt_paths: vars.list_or("HOST_CHECK_EXEMPT_PATHS", &probe_paths),
+            pg_config_notify: vars.bool("ENABLE_PG_CONFIG_NOTIFY", false),
+            config_notify_channel: vars
+                .get("CONFIG_NOTIFY_CHANNEL")
+                .unwrap_or_else(|| "config_reload".to_string()),
+            dependency_close_timeout_secs: vars.parse("DEPENDENCY_CLOSE_TIMEOUT_SECS", 5),
+            pre_stop_delay_secs: vars.parse("PRE_STOP_DELAY_SECS", 0),
+            tcp_nodelay: vars.bool("TCP_NODELAY", true),
+            so_reuseaddr: vars.bool("SO_REUSEADDR", true),
+            postgres: PostgresConfig {
+                host: vars
+                    .get("POSTGRES_HOST")
+                    .unwrap_or_else(|| "postgres".to_string()),
+                port: vars.parse("POSTGRES_PORT", 5432),
+                user: vars
+                    .get("POSTGRES_USER")
+                    .unwrap_or_else(|| "dev_admin".to_string()),
+                password: vars.get("POSTGRES_PASSWORD").unwrap_or_default(),
+                dbname: vars
+                    .get("POSTGRES_DB")
+                    .unwrap_or_else(|| "dev_database".to_string()),
+            },
+            proxy: ProxyConfig {
+                outbound: vars.any(&["OUTBOUND_PROXY"]),
+                http: vars.any(&["HTTP_PROXY", "http_proxy"]),
+                https: vars.any(&["HTTPS_PROXY", "https_proxy"]),
+                no_proxy: vars.any(&["NO_PROXY", "no_proxy"]),
+            },
+            vault_tls: VaultTls {
+                client_cert: vars.any(&["VAULT_CLIENT_CERT"]),
+                client_key: vars.any(&["VAULT_CLIENT_KEY"]),
+                ca_cert: vars.any(&["VAULT_CA_CERT"]),
+            },
+            request_timeout_ms: vars.parse("REQUEST_TIMEOUT_MS", 30_000),
+            request_timeout_max_ms: vars.parse("REQUEST_TIMEOUT_MAX_MS", 60_000),
+            trailing_slash_mode: vars.parse("TRAILING_SLASH_MODE", TrailingSlashMode::Trim),
+            route_prefix: normalize_prefix(&vars.get("ROUTE_PREFIX").unwrap_or_default()),
+            json_field_case: vars.parse("JSON_FIELD_CASE", FieldCase::Snake),
+            json_pretty_default: vars.bool("JSON_PRETTY_DEFAULT", false),
+            timestamp_tz: vars.parse("TIMESTAMP_TZ", TimestampTz::Utc),
+            enabled_checks: enabled_checks(&vars),
+            health_history_size: vars.parse("HEALTH_HISTORY_SIZE", 100),
+            check_max_concurrency: Check::ALL
+                .into_iter()
+                .filter_map(|check| {
+                    let name = format!("{}_MAX_CONCURRENCY", check.name().to_ascii_uppercase());
+                    vars.parse_opt(&name).map(|limit| (check, limit))
+                })
+                .collect(),
+            check_timeout_ms: vars.parse("HEALTH_CHECK_TIMEOUT_MS", 4000),
+            check_timeout_policy: Check::ALL
+                .into_iter()
+                .filter_map(|check| {
+                    let name = format!(
+                        "HEALTH_{}_TIMEOUT_POLICY",
+                        check.name().to_ascii_uppercase()
+                    );
+                    vars.parse_opt(&name).map(|policy| (check, policy))
+                })
+                .collect(),
+            check_dependencies: check_dependencies(&vars),
+            http_checks: http_checks(&vars),
+            grpc_health_addr: vars.get("GRPC_HEALTH_ADDR"),
+            grpc_health_service: vars.get("GRPC_HEALTH_SERVICE").unwrap_or_default(),
+            enable_grpc_health: vars.bool("ENABLE_GRPC_HEALTH", false),
+            grpc_port: vars.parse("GRPC_PORT", 50051),
+            grpc_health_interval_secs: vars.parse("GRPC_HEALTH_INTERVAL_SECS", 5),
+            memory_ready_limit_mb: vars.parse_opt("MEMORY_READY_LIMIT_MB"),
+            readiness_failure_threshold: vars.parse("READINESS_FAILURE_THRESHOLD", 0),
+            readiness_failure_count: vars.parse("READINESS_FAILURE_COUNT", 1),
+            readiness_success_count: vars.parse("READINESS_SUCCESS_COUNT", 1),
+            statsd_addr: vars.get("STATSD_ADDR"),
+            statsd_prefix: vars
+                .get("STATSD_PREFIX")
+                .unwrap_or_else(|| "rust_api".to_string()),
+            statsd_sample_rate: vars.parse("STATSD_SAMPLE_RATE", 1.0),
+            pushgateway_url: vars.any(&["PUSHGATEWAY_URL"]),
+            pushgateway_job: vars
+                .get("PUSHGATEWAY_JOB")
+                .unwrap_or_else(|| "rust-api".to_string()),
+            pushgateway_grouping: pushgateway_grouping(&vars),
+            pushgateway_interval_secs: vars.parse("PUSHGATEWAY_INTERVAL_SECS", 15),
+            registry_url: vars.any(&["REGISTRY_URL"]),
+            registry_kind: vars.parse("REGISTRY_KIND", RegistryKind::Http),
+            registry_service: vars
+                .get("REGISTRY_SERVICE")
+                .unwrap_or_else(|| "rust-api".to_string()),
+            registry_address: vars
+                .any(&["REGISTRY_ADDRESS", "HOSTNAME"])
+                .unwrap_or_else(|| "localhost".to_string()),
+            heartbeat_interval_secs: vars.parse("HEARTBEAT_INTERVAL_SECS", 10),
+            metric_label_max_len: vars.parse("METRIC_LABEL_MAX_LEN", 128),
+            metrics_auth_token: vars.any(&["METRICS_AUTH_TOKEN"]).map(Secret),
+            hmac_secret: vars.any(&["HMAC_SECRET"]).map(Secret),
+            hmac_max_age_secs: vars.parse("HMAC_MAX_AGE_SECS", 300),
+            hmac_exempt_paths: vars.list_or("HMAC_EXEMPT_PATHS", &probe_paths),
+            max_body_bytes: vars.parse("MAX_BODY_BYTES", 256 * 1024),
+            record_sample_rate: vars.parse("RECORD_SAMPLE_RATE", 0.0),
+            record_file: vars
+                .get("RECORD_FILE")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from("/tmp/rust-api-requests.jsonl")),
+            record_max_bytes: vars.parse("RECORD_MAX_BYTES", 10 * 1024 * 1024),
+            record_body_max_bytes: vars.parse("RECORD_BODY_MAX_BYTES", 4096),
+        }
+    }
+}
+
+/// The settings [`Config::from_env`] gives with no variables set.
+impl Default for Config {
+    fn default() -> Self {
+        Config::from_lookup(&|_| None)
+    }
+}
+
+/// Settings that can be reloaded without a restart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HotConfig {
+    /// Answer non-health routes with 503 (`MAINTENANCE_MODE`).
+    pub maintenance_mode: bool,
+    /// `Retry-After` seconds sent while in maintenance (`MAINTENANCE_RETRY_AFTER`).
+    pub maintenance_retry_after: u64,
+}
+
+impl Default for HotConfig {
+    fn default() -> Self {
+        HotConfig {
+            maintenance_mode: false,
+            maintenance_retry_after: 60,
+        }
+    }
+}
+
+impl HotConfig {
+    /// Loads from the environment and `CONFIG_FILE`, if set.
+    pub fn load() -> Result<HotConfig, String> {
+        let overrides = match env::var("CONFIG_FILE") {
+            Ok(path) => {
+                let contents = fs::read_to_string(&path)
+                    .map_err(|e| format!("failed to read CONFIG_FILE {}: {}", path, e))?;
+                parse_env_file(&contents)
+            }
+            Err(_) => HashMap::new(),
+        };
+        Ok(HotConfig::from_lookup(|name| {
+            overrides.get(name).cloned().or_else(|| env::var(name).ok())
+        }))
+    }
+
+    /// Reloads into `hot`, keeping the current settings if loading fails.
+    /// `trigger` names what asked for the reload, for the log.
+    pub fn reload_into(hot: &RwLock<HotConfig>, trigger: &str) {
+        match HotConfig::load() {
+            Ok(reloaded) => {
+                log::info!("{}: reloaded config {:?}", trigger, reloaded);
+                *hot.write().unwrap_or_else(|e| e.into_inner()) = reloaded;
+            }
+            Err(e) => log::error!("{}: config reload failed, keeping current: {}", trigger, e),
+        }
+    }
+
+    fn from_lookup(get: impl Fn(&str) -> Option<String>) -> HotConfig {
+        let defaults = HotConfig::default();
+        HotConfig {
+            maintenance_mode: get("MAINTENANCE_MODE")
+                .map(|v| parse_bool(&v))
+                .unwrap_or(defaults.maintenance_mode),
+            maintenance_retry_after: get("MAINTENANCE_RETRY_AFTER")
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(defaults.maintenance_retry_after),
+        }
+    }
+}
+
+/// Parses `KEY=VALUE` lines, skipping blanks and `#` comments.
+fn parse_env_file(contents: &str) -> HashMap<String, String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().trim_matches('"').to_string()))
+        .collect()
+}
+
+/// A check is enabled when `HEALTH_<NAME>_ENABLED` says so, or, if that is
+/// unset, when it is in `HEALTH_ENABLED_CHECKS` (or that is unset too).
+/// Checks for optional downstreams are only on by default once configured.
+fn enabled_checks(vars: &Vars) -> Vec<Check> {
+    let allowlist = vars.get("HEALTH_ENABLED_CHECKS").map(|_| {
+        vars.list("HEALTH_ENABLED_CHECKS")
+            .iter()
+            .filter_map(|name| match name.parse::<Check>() {
+                Ok(check) => Some(check),
+                Err(e) => {
+                    log::warn!("Ignoring HEALTH_ENABLED_CHECKS entry: {}", e);
+                    None
+                }
+            })
+            .collect::<Vec<_>>()
+    });
+
+    Check::ALL
+        .into_iter()
+        .filter(|check| {
+            let flag = format!("HEALTH_{}_ENABLED", check.name().to_ascii_uppercase());
+            match vars.get(&flag) {
+                Some(v) => parse_bool(&v),
+                None => match &allowlist {
+                    Some(list) => list.contains(check),
+                    None => match check {
+                        Check::Grpc => vars.get("GRPC_HEALTH_ADDR").is_some(),
+                        Check::Http => vars.get("HTTP_CHECKS").is_some(),
+                        _ => true,
+                    },
+                },
+            }
+        })
+        .collect()
+}
+
+/// `api/`, `/api` and `/api/` all become `/api`; blank or `/` becomes empty.
+fn normalize_prefix(prefix: &str) -> String {
+    let trimmed = prefix.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", trimmed)
+    }
+}
+
+/// Parses and validates `HTTP_CHECKS` entries; invalid or duplicate ones are
+/// skipped with a warning.
+fn http_checks(vars: &Vars) -> Vec<HttpCheck> {
+    let mut checks: Vec<HttpCheck> = Vec::new();
+    for entry in vars.list("HTTP_CHECKS") {
+        match entry.parse::<HttpCheck>() {
+            Ok(check) if checks.iter().any(|c| c.name == check.name) => {
+                log::warn!("Ignoring duplicate HTTP_CHECKS entry '{}'", check.name);
+            }
+            Ok(check) => checks.push(check),
+            Err(e) => log::warn!("Ignoring invalid HTTP_CHECKS entry: {}", e),
+        }
+    }
+    checks
+}
+
+/// Parses `name` (on) and `name=<bool>` entries, e.g. `new_cache,beta=false`.
+fn feature_flags(vars: &Vars) -> HashMap<String, bool> {
+    vars.list("FEATURE_FLAGS")
+        .iter()
+        .map(|entry| match entry.split_once('=') {
+            Some((name, value)) => (name.trim().to_string(), parse_bool(value)),
+            None => (entry.clone(), true),
+        })
+        .filter(|(name, _)| {
+            if name.is_empty() {
+                log::warn!("Ignoring FEATURE_FLAGS entry with no name");
+            }
+            !name.is_empty()
+        })
+        .collect()
+}
+
+/// Parses `label=value` pairs, e.g. `instance=pod-7,env=staging`.
+fn pushgateway_grouping(vars: &Vars) -> Vec<(String, String)> {
+    let valid_label = |label: &str| {
+        label.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && label != "job"
+    };
+    vars.list("PUSHGATEWAY_GROUPING")
+        .iter()
+        .filter_map(|entry| {
+            let parsed = entry
+                .split_once('=')
+                .map(|(label, value)| (label.trim().to_string(), value.trim().to_string()))
+                .filter(|(label, _)| valid_label(label));
+            if parsed.is_none() {
+                log::warn!("Ignoring invalid PUSHGATEWAY_GROUPING entry '{}'", entry);
+            }
+            parsed
+        })
+        .collect()
+}
+
+fn cors_policies(vars: &Vars) -> Vec<CorsPolicy> {
+    vars.list("CORS_POLICIES")
+        .iter()
+        .filter_map(|entry| match entry.parse() {
+            Ok(policy) => Some(policy),
+            Err(e) => {
+                log::warn!("Ignoring invalid CORS_POLICIES entry: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Parses `path:key1|key2` entries, e.g.
+/// `secret/data/app:username|password,secret/data/tls:cert`.
+fn required_secrets(vars: &Vars) -> Vec<RequiredSecret> {
+    vars.list("REQUIRED_SECRETS")
+        .iter()
+        .filter_map(|entry| match entry.parse() {
+            Ok(secret) => Some(secret),
+            Err(e) => {
+                log::warn!("Ignoring invalid REQUIRED_SECRETS entry: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Parses `dependent:prerequisite` pairs, e.g. `grpc:vault,tls_expiry:vault`.
+fn check_dependencies(vars: &Vars) -> Vec<(Check, Check)> {
+    vars.list("HEALTH_CHECK_DEPENDENCIES")
+        .iter()
+        .filter_map(|entry| {
+            let parsed = entry.split_once(':').and_then(|(dependent, prerequisite)| {
+                Some((dependent.parse().ok()?, prerequisite.parse().ok()?))
+            });
+            if parsed.is_none() {
+                log::warn!(
+                    "Ignoring invalid HEALTH_CHECK_DEPENDENCIES entry '{}'",
+                    entry
+                );
+            }
+            parsed
+        })
+        .collect()
+}
+
+pub fn parse_bool(v: &str) -> bool {
+    matches!(
+        v.trim().to_ascii_lowercase().as_str(),
+        "1" | "true" | "yes" | "on"
+    )
+}
+
+/// Where [`Config`] reads its variables from.
+struct Vars<'a>(&'a dyn Fn(&str) -> Option<String>);
+
+impl Vars<'_> {
+    fn get(&self, name: &str) -> Option<String> {
+        (self.0)(name)
+    }
+
+    fn bool(&self, name: &str, default: bool) -> bool {
+        self.get(name).map(|v| parse_bool(&v)).unwrap_or(default)
+    }
+
+    /// Like [`Vars::bool`] for a profile-supplied default, logging when the
+    /// variable overrides it.
+    fn profile_bool(&self, name: &str, profile: Profile, default: bool) -> bool {
+        let value = self.bool(name, default);
+        if value != default {
+            log::info!(
+                "{}={} overrides the {} profile default ({})",
+                name,
+                value,
+                profile.name(),
+                default
+            );
+        }
+        value
+    }
+
+    fn parse<T: std::str::FromStr>(&self, name: &str, default: T) -> T {
+        match self.get(name) {
+            Some(v) => v.trim().parse().unwrap_or_else(|_| {
+                log::warn!("Ignoring invalid {}='{}'", name, v);
+                default
+            }),
+            None => default,
+        }
+    }
+
+    /// Like [`Vars::parse`], but `None` when the variable is unset or invalid.
+    fn parse_opt<T: std::str::FromStr>(&self, name: &str) -> Option<T> {
+        let v = self.get(name)?;
+        let parsed = v.trim().parse().ok();
+        if parsed.is_none() {
+            log::warn!("Ignoring invalid {}='{}'", name, v);
+        }
+        parsed
+    }
+
+    /// The first of `names` that is set to a non-empty value.
+    fn any(&self, names: &[&str]) -> Option<String> {
+        names
+            .iter()
+            .filter_map(|name| self.get(name))
+            .find(|v| !v.trim().is_empty())
+    }
+
+    /// Splits a comma-separated variable, dropping empty entries.
+    fn list(&self, name: &str) -> Vec<String> {
+        self.get(name)
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect()
+    }
+
+    /// Like [`Vars::list`], but falls back to `default` when the variable is
+    /// unset. Set it to an empty string to get an empty list.
+    fn list_or(&self, name: &str, default: &[&str]) -> Vec<String> {
+        if self.get(name).is_some() {
+            self.list(name)
+        } else {
+            default.iter().map(|s| s.to_string()).collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hot_config_from_env_file() {
+        let vars = parse_env_file(
+            "# maintenance window\nMAINTENANCE_MODE=true\n\nMAINTENANCE_RETRY_AFTER = \"300\"\n",
+        );
+        let hot = HotConfig::from_lookup(|name| vars.get(name).cloned());
+        assert!(hot.maintenance_mode);
+        assert_eq!(hot.maintenance_retry_after, 300);
+    }
+
+    #[test]
+    fn test_normalize_prefix() {
+        assert_eq!(normalize_prefix("api/"), "/api");
+        assert_eq!(normalize_prefix("/rust-api"), "/rust-api");
+        assert_eq!(normalize_prefix("/"), "");
+        assert_eq!(normalize_prefix(""), "");
+    }
+
+    #[test]
+    fn test_profiles() {
+        assert_eq!("production".parse(), Ok(Profile::Prod));
+        assert_eq!("Staging".parse(), Ok(Profile::Staging));
+        assert_eq!("dev".parse(), Ok(Profile::Dev));
+        assert!("qa".parse::<Profile>().is_err());
+
+        assert!(Profile::Dev.permissive_cors() && Profile::Dev.debug_endpoints());
+        assert!(!Profile::Staging.permissive_cors() && Profile::Staging.debug_endpoints());
+        assert!(!Profile::Prod.permissive_cors() && !Profile::Prod.debug_endpoints());
+    }
+
+    #[test]
+    fn test_missing_required() {
+        let vars: HashMap<&str, &str> = HashMap::from([
+            ("VAULT_ADDR", "https://vault:8200"),
+            ("VAULT_TOKEN_FILE", "/vault/token"),
+            ("VAULT_KV_MOUNT", " "),
+        ]);
+        let lookup = |name: &str| vars.get(name).map(|v| v.to_string());
+
+        assert!(missing_required(Profile::Dev, lookup).is_empty());
+        assert!(missing_required(Profile::Staging, lookup).is_empty());
+        assert_eq!(
+            missing_required(Profile::Prod, lookup),
+            vec!["VAULT_KV_MOUNT", "CORS_ALLOWED_ORIGINS"]
+        );
+        assert_eq!(
+            missing_required(Profile::Staging, |_| None),
+            vec!["VAULT_ADDR", "VAULT_TOKEN or VAULT_TOKEN_FILE"]
+        );
+    }
+
+    #[test]
+    fn test_hot_config_defaults() {
+        let hot = HotConfig::from_lookup(|_| None);
+        assert_eq!(hot, HotConfig::default());
+    }
+
+    #[test]
+    fn test_config_defaults() {
+        let config = Config::default();
+        assert_eq!(config.request_timeout_ms, 30_000);
+        assert_eq!(config.check_timeout_ms, 4000);
+        assert_eq!(config.required_secrets_timeout_ms, 5000);
+        assert_eq!(config.tls_expiry_warn_days, 30);
+        assert_eq!(config.health_history_size, 100);
+        assert_eq!(
+            (
+                config.readiness_failure_count,
+                config.readiness_success_count
+            ),
+            (1, 1)
+        );
+        assert_eq!(config.enabled_checks, vec![Check::Vault, Check::TlsExpiry]);
+        assert_eq!(
+            config.host_check_exempt_paths,
+            vec!["/health", "/v1/health", "/metrics"]
+        );
+
+        let config = Config::from_lookup(&|name| (name == "APP_ENV").then(|| "prod".to_string()));
+        assert!(!config.debug_endpoints && !config.cors_permissive);
+    }
+}
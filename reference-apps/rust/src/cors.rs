@@ -0,0 +1,181 @@
+//! CORS. By default one policy covers every route: any origin with
+//! `CORS_PERMISSIVE`, else only `CORS_ALLOWED_ORIGINS`.
+//!
+//! `CORS_POLICIES` adds policies keyed by path prefix, e.g.
+//! `/health=*,/api=https://app.example.com|https://admin.example.com`.
+//! A request uses the policy with the longest prefix matching its path on
+//! segment boundaries (`/api` covers `/api` and `/api/items`, not
+//! `/apix`); paths no policy covers get the default policy.
+
+use actix_cors::Cors;
+
+use crate::config::Config;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origins {
+    Any,
+    List(Vec<String>),
+}
+
+impl Origins {
+    fn allows(&self, origin: &str) -> bool {
+        match self {
+            Origins::Any => true,
+            Origins::List(origins) => origins.iter().any(|o| o.eq_ignore_ascii_case(origin)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorsPolicy {
+    pub prefix: String,
+    pub origins: Origins,
+}
+
+impl std::str::FromStr for CorsPolicy {
+    type Err = String;
+
+    fn from_str(entry: &str) -> Result<Self, Self::Err> {
+        let (prefix, origins) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("'{}' is not prefix=origins", entry.trim()))?;
+        let prefix = prefix.trim();
+        if !prefix.starts_with('/') {
+            return Err(format!("prefix '{}' must start with /", prefix));
+        }
+        let origins: Vec<String> = origins
+            .split('|')
+            .map(str::trim)
+            .filter(|o| !o.is_empty())
+            .map(String::from)
+            .collect();
+        let origins = if origins.iter().any(|o| o == "*") {
+            Origins::Any
+        } else {
+            Origins::List(origins)
+        };
+        Ok(CorsPolicy {
+            prefix: match prefix.trim_end_matches('/') {
+                "" => "/".to_string(),
+                trimmed => trimmed.to_string(),
+            },
+            origins,
+        })
+    }
+}
+
+impl CorsPolicy {
+    fn covers(&self, path: &str) -> bool {
+        self.prefix == "/"
+            || path
+                .strip_prefix(&self.prefix)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+}
+
+/// The most specific policy covering `path`, if any does.
+fn policy_for<'a>(policies: &'a [CorsPolicy], path: &str) -> Option<&'a CorsPolicy> {
+    policies
+        .iter()
+        .filter(|policy| policy.covers(path))
+        .max_by_key(|policy| policy.prefix.len())
+}
+
+pub fn build(config: &Config) -> Cors {
+    if config.cors_policies.is_empty() {
+        return if config.cors_permissive {
+            Cors::permissive()
+        } else {
+            config
+                .cors_allowed_origins
+                .iter()
+                .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+                .allow_any_method()
+                .allow_any_header()
+        };
+    }
+
+    let fallback = if config.cors_permissive {
+        Origins::Any
+    } else {
+        Origins::List(config.cors_allowed_origins.clone())
+    };
+    let policies = config.cors_policies.clone();
+    Cors::default()
+        .allowed_origin_fn(move |origin, head| {
+            let origins = policy_for(&policies, head.uri.path()).map_or(&fallback, |p| &p.origins);
+            origin.to_str().is_ok_and(|origin| origins.allows(origin))
+        })
+        .allow_any_method()
+        .allow_any_header()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header::{ACCESS_CONTROL_ALLOW_ORIGIN, ORIGIN};
+    use actix_web::test::{self};
+    use actix_web::{web, App, HttpResponse};
+
+    #[test]
+    fn test_parse_and_pick_most_specific_policy() {
+        let policies: Vec<CorsPolicy> = [
+            "/=https://a.example",
+            "/api=https://b.example|https://c.example",
+            "/api/public/=*",
+        ]
+        .iter()
+        .map(|entry| entry.parse().unwrap())
+        .collect();
+        assert_eq!(policies[2].prefix, "/api/public");
+        assert_eq!(policies[2].origins, Origins::Any);
+
+        let prefix = |path| policy_for(&policies, path).map(|p| p.prefix.as_str());
+        assert_eq!(prefix("/api/public/items"), Some("/api/public"));
+        assert_eq!(prefix("/api/items"), Some("/api"));
+        assert_eq!(prefix("/apix"), Some("/"));
+        assert_eq!(policy_for(&policies[1..], "/health"), None);
+
+        assert!("api=*".parse::<CorsPolicy>().is_err());
+        assert!("/api".parse::<CorsPolicy>().is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_policies_apply_per_path() {
+        let config = Config {
+            cors_permissive: false,
+            cors_allowed_origins: vec!["https://app.example".to_string()],
+            cors_policies: vec![
+                "/health=*".parse().unwrap(),
+                "/api=https://admin.example".parse().unwrap(),
+            ],
+            ..Config::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .wrap(build(&config))
+                .route("/health", web::get().to(HttpResponse::Ok))
+                .route("/api/items", web::get().to(HttpResponse::Ok))
+                .route("/", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        for (path, origin, allowed) in [
+            ("/health", "https://anywhere.example", true),
+            ("/api/items", "https://admin.example", true),
+            ("/api/items", "https://app.example", false),
+            ("/", "https://app.example", true),
+            ("/", "https://admin.example", false),
+        ] {
+            let req = test::TestRequest::get()
+                .uri(path)
+                .insert_header((ORIGIN, origin))
+                .to_request();
+            let resp = test::try_call_service(&app, req).await;
+            let echoed = resp
+                .ok()
+                .and_then(|r| r.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).cloned());
+            assert_eq!(echoed.is_some(), allowed, "{} from {}", path, origin);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Pre-stop draining. With `PRE_STOP_DELAY_SECS` set, SIGTERM first flips
+//! `/health/ready` to 503 while the server keeps serving, so load balancers
+//! deregister the instance before connections are drained and the server
+//! stops. SIGINT and SIGQUIT still stop at once, even mid-drain, and so
+//! does a second SIGTERM.
+//!
+//! On exit a shutdown report is logged: uptime, requests served, peak
+//! in-flight requests, and how long shutdown took from the signal.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+use crate::metrics::Metrics;
+
+/// Set once draining starts; readiness reports not ready from then on.
+#[derive(Debug, Default)]
+pub struct Draining {
+    draining: AtomicBool,
+    stopping: OnceLock<Instant>,
+}
+
+impl Draining {
+    pub fn start(&self) {
+        self.stopping();
+        self.draining.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    /// Notes when shutdown began; later calls keep the first time.
+    fn stopping(&self) {
+        self.stopping.get_or_init(Instant::now);
+    }
+
+    /// Time since shutdown began, if a signal started it.
+    fn stopping_for(&self) -> Option<Duration> {
+        self.stopping.get().map(Instant::elapsed)
+    }
+}
+
+/// The report logged as the process exits. `drain_ms` is null when the
+/// server stopped without a signal.
+pub fn shutdown_report(uptime_secs: u64, metrics: &Metrics, draining: &Draining) -> Value {
+    serde_json::json!({
+        "uptime_secs": uptime_secs,
+        "requests_served": metrics.requests_served(),
+        "peak_in_flight": metrics.peak_in_flight(),
+        "drain_ms": draining.stopping_for().map(|d| d.as_millis() as u64),
+    })
+}
+
+/// Takes over the server's signal handling: SIGTERM drains for `delay` (if
+/// any) and then stops gracefully; any signal during the drain stops at
+/// once. The server must be built with `disable_signals()`.
+#[cfg(unix)]
+pub fn handle_signals(
+    draining: actix_web::web::Data<Draining>,
+    server: actix_web::dev::ServerHandle,
+    delay: std::time::Duration,
+) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    actix_web::rt::spawn(async move {
+        let (Ok(mut term), Ok(mut int), Ok(mut quit)) = (
+            signal(SignalKind::terminate()),
+            signal(SignalKind::interrupt()),
+            signal(SignalKind::quit()),
+        ) else {
+            log::error!("Failed to install signal handlers; stopping server");
+            server.stop(true).await;
+            return;
+        };
+
+        tokio::select! {
+            _ = term.recv() => {
+                draining.start();
+                if !delay.is_zero() {
+                    log::info!(
+                        "Draining started: reporting not ready for {}s before shutdown",
+                        delay.as_secs()
+                    );
+                    let interrupted = tokio::select! {
+                        _ = tokio::time::sleep(delay) => false,
+                        _ = term.recv() => true,
+                        _ = int.recv() => true,
+                        _ = quit.recv() => true,
+                    };
+                    if interrupted {
+                        log::info!("Signal received while draining; stopping server now");
+                        server.stop(false).await;
+                        return;
+                    }
+                    log::info!("Drain complete; stopping server");
+                }
+                server.stop(true).await;
+            }
+            _ = int.recv() => {
+                draining.stopping();
+                server.stop(false).await;
+            }
+            _ = quit.recv() => {
+                draining.stopping();
+                server.stop(false).await;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_shutdown_report() {
+        let metrics = Metrics::new(&Config::default());
+        metrics.record("GET", "/health", 200, Duration::from_millis(2));
+        metrics.record("POST", "/echo", 400, Duration::from_millis(2));
+        let draining = Draining::default();
+
+        let report = shutdown_report(42, &metrics, &draining);
+        assert_eq!(report["uptime_secs"], 42);
+        assert_eq!(report["requests_served"], 2);
+        assert_eq!(report["peak_in_flight"], 0);
+        assert!(report["drain_ms"].is_null());
+
+        draining.start();
+        assert!(draining.is_draining());
+        assert!(shutdown_report(42, &metrics, &draining)["drain_ms"].is_u64());
+    }
+}
@@ -0,0 +1,105 @@
+//! `POST /echo`: sends the JSON body back, for checking what proxies and
+//! body-size limits let through the whole middleware stack.
+
+use actix_web::http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use futures_util::StreamExt;
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::error::ApiError;
+
+/// Reads the body, giving up as soon as it passes `limit` bytes. A
+/// `Content-Length` over the limit is refused before anything is read.
+async fn read_limited(
+    req: &HttpRequest,
+    mut payload: web::Payload,
+    limit: usize,
+) -> Result<web::BytesMut, ApiError> {
+    let too_large = ApiError::PayloadTooLarge { limit_bytes: limit };
+    let declared = req
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    if declared.is_some_and(|len| len > limit) {
+        return Err(too_large);
+    }
+
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|e| ApiError::InvalidJson {
+            reason: e.to_string(),
+        })?;
+        if body.len() + chunk.len() > limit {
+            return Err(too_large);
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+#[post("/echo")]
+pub async fn echo(
+    req: HttpRequest,
+    payload: web::Payload,
+    config: web::Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+    let body = read_limited(&req, payload, config.max_body_bytes).await?;
+    let json: Value = serde_json::from_slice(&body).map_err(|e| ApiError::InvalidJson {
+        reason: e.to_string(),
+    })?;
+    let content_type = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok());
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "body": json,
+        "received_bytes": body.len(),
+        "content_type": content_type
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    #[actix_web::test]
+    async fn test_echo_round_trip_and_limit() {
+        let config = Config {
+            max_body_bytes: 32,
+            ..Config::default()
+        };
+        let app =
+            test::init_service(App::new().app_data(web::Data::new(config)).service(echo)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/echo")
+            .insert_header((CONTENT_TYPE, "application/json"))
+            .set_payload(r#"{"hello":"world"}"#)
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["body"]["hello"], "world");
+        assert_eq!(body["received_bytes"], 17);
+        assert_eq!(body["content_type"], "application/json");
+
+        let req = test::TestRequest::post()
+            .uri("/echo")
+            .set_payload(format!(r#"{{"padding":"{}"}}"#, "x".repeat(64)))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 413);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "payload_too_large");
+        assert_eq!(body["limit_bytes"], 32);
+
+        let req = test::TestRequest::post()
+            .uri("/echo")
+            .set_payload("not json")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+    }
+}
@@ -0,0 +1,269 @@
+//! API errors and their stable, client-facing codes.
+//!
+//! Every error body is `{"error": <code>, "message": ..., ...}`. Codes are
+//! part of the API contract: add new ones freely, but never rename or reuse
+//! one. The full list is served at `/debug/error-codes`.
+
+use std::fmt;
+
+use actix_web::http::header::WWW_AUTHENTICATE;
+use actix_web::http::StatusCode;
+use actix_web::{get, HttpResponse, Responder, ResponseError};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+pub mod codes {
+    pub const INJECTED_FAULT: &str = "injected_fault";
+    pub const INVALID_JSON: &str = "invalid_json";
+    pub const INVALID_SIGNATURE: &str = "invalid_signature";
+    pub const MISDIRECTED_REQUEST: &str = "misdirected_request";
+    pub const PAYLOAD_TOO_LARGE: &str = "payload_too_large";
+    pub const REQUEST_TIMEOUT: &str = "request_timeout";
+    pub const UNAUTHORIZED: &str = "unauthorized";
+    pub const UNKNOWN_CHECK: &str = "unknown_check";
+}
+
+/// A documented error code, as listed by `/debug/error-codes`.
+#[derive(Debug, Serialize)]
+pub struct ErrorCode {
+    pub code: &'static str,
+    pub status: u16,
+    pub description: &'static str,
+}
+
+pub const ERROR_CODES: &[ErrorCode] = &[
+    ErrorCode {
+        code: codes::INJECTED_FAULT,
+        status: 500,
+        description: "Failure injected by chaos mode (CHAOS_ENABLED)",
+    },
+    ErrorCode {
+        code: codes::INVALID_JSON,
+        status: 400,
+        description: "Request body is not valid JSON",
+    },
+    ErrorCode {
+        code: codes::INVALID_SIGNATURE,
+        status: 401,
+        description: "Missing, stale, replayed or wrong HMAC request signature",
+    },
+    ErrorCode {
+        code: codes::MISDIRECTED_REQUEST,
+        status: 421,
+        description: "Host header does not match an allowed host",
+    },
+    ErrorCode {
+        code: codes::PAYLOAD_TOO_LARGE,
+        status: 413,
+        description: "Request body exceeds MAX_BODY_BYTES",
+    },
+    ErrorCode {
+        code: codes::REQUEST_TIMEOUT,
+        status: 504,
+        description: "Request exceeded its time budget",
+    },
+    ErrorCode {
+        code: codes::UNAUTHORIZED,
+        status: 401,
+        description: "Missing or wrong credentials for this endpoint",
+    },
+    ErrorCode {
+        code: codes::UNKNOWN_CHECK,
+        status: 404,
+        description: "No health check with that name",
+    },
+];
+
+#[derive(Debug)]
+pub enum ApiError {
+    /// A deliberate failure from chaos mode.
+    InjectedFault,
+    InvalidJson {
+        reason: String,
+    },
+    InvalidSignature {
+        reason: &'static str,
+    },
+    MisdirectedRequest,
+    PayloadTooLarge {
+        limit_bytes: usize,
+    },
+    /// The request itself is dropped with the handler future, so this is
+    /// rendered by actix rather than returned from a handler.
+    RequestTimeout {
+        timeout_ms: u64,
+    },
+    /// Sent with a `WWW-Authenticate` challenge for `realm`.
+    Unauthorized {
+        realm: &'static str,
+    },
+    UnknownCheck {
+        name: String,
+    },
+}
+
+impl ApiError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::InjectedFault => codes::INJECTED_FAULT,
+            ApiError::InvalidJson { .. } => codes::INVALID_JSON,
+            ApiError::InvalidSignature { .. } => codes::INVALID_SIGNATURE,
+            ApiError::MisdirectedRequest => codes::MISDIRECTED_REQUEST,
+            ApiError::PayloadTooLarge { .. } => codes::PAYLOAD_TOO_LARGE,
+            ApiError::RequestTimeout { .. } => codes::REQUEST_TIMEOUT,
+            ApiError::Unauthorized { .. } => codes::UNAUTHORIZED,
+            ApiError::UnknownCheck { .. } => codes::UNKNOWN_CHECK,
+        }
+    }
+
+    /// Variant-specific fields added to the body next to `error`/`message`.
+    fn details(&self) -> Map<String, Value> {
+        let mut details = Map::new();
+        match self {
+            ApiError::RequestTimeout { timeout_ms } => {
+                details.insert("timeout_ms".to_string(), (*timeout_ms).into());
+            }
+            ApiError::PayloadTooLarge { limit_bytes } => {
+                details.insert("limit_bytes".to_string(), (*limit_bytes).into());
+            }
+            _ => {}
+        }
+        details
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::InjectedFault => write!(f, "failure injected by chaos mode"),
+            ApiError::InvalidJson { reason } => write!(f, "invalid JSON body: {}", reason),
+            ApiError::InvalidSignature { reason } => {
+                write!(f, "request signature rejected: {}", reason)
+            }
+            ApiError::MisdirectedRequest => {
+                write!(f, "Host header does not match an allowed host")
+            }
+            ApiError::PayloadTooLarge { limit_bytes } => {
+                write!(f, "request body exceeds {} bytes", limit_bytes)
+            }
+            ApiError::RequestTimeout { timeout_ms } => {
+                write!(f, "request timed out after {}ms", timeout_ms)
+            }
+            ApiError::Unauthorized { realm } => write!(f, "{} requires credentials", realm),
+            ApiError::UnknownCheck { name } => write!(f, "unknown health check '{}'", name),
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::InjectedFault => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::InvalidJson { .. } => StatusCode::BAD_REQUEST,
+            ApiError::InvalidSignature { .. } => StatusCode::UNAUTHORIZED,
+            ApiError::MisdirectedRequest => StatusCode::MISDIRECTED_REQUEST,
+            ApiError::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::RequestTimeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+            ApiError::Unauthorized { .. } => StatusCode::UNAUTHORIZED,
+            ApiError::UnknownCheck { .. } => StatusCode::NOT_FOUND,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut body = Map::new();
+        body.insert("error".to_string(), self.code().into());
+        body.insert("message".to_string(), self.to_string().into());
+        body.extend(self.details());
+        let mut resp = HttpResponse::build(self.status_code());
+        if let ApiError::Unauthorized { realm } = self {
+            resp.insert_header((
+                WWW_AUTHENTICATE,
+                format!("Bearer realm=\"{0}\", Basic realm=\"{0}\"", realm),
+            ));
+        }
+        resp.json(body)
+    }
+}
+
+#[get("/debug/error-codes")]
+pub async fn error_codes() -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({ "error_codes": ERROR_CODES }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// One of each variant. The match makes adding a variant without
+    /// listing it here a compile error.
+    fn every_variant() -> Vec<ApiError> {
+        let all = vec![
+            ApiError::InjectedFault,
+            ApiError::InvalidJson {
+                reason: "EOF".to_string(),
+            },
+            ApiError::InvalidSignature {
+                reason: "signature mismatch",
+            },
+            ApiError::MisdirectedRequest,
+            ApiError::PayloadTooLarge { limit_bytes: 1 },
+            ApiError::RequestTimeout { timeout_ms: 1 },
+            ApiError::Unauthorized { realm: "metrics" },
+            ApiError::UnknownCheck {
+                name: "redis".to_string(),
+            },
+        ];
+        for e in &all {
+            match e {
+                ApiError::InjectedFault
+                | ApiError::InvalidJson { .. }
+                | ApiError::InvalidSignature { .. }
+                | ApiError::MisdirectedRequest
+                | ApiError::PayloadTooLarge { .. }
+                | ApiError::RequestTimeout { .. }
+                | ApiError::Unauthorized { .. }
+                | ApiError::UnknownCheck { .. } => {}
+            }
+        }
+        all
+    }
+
+    fn is_snake_case(code: &str) -> bool {
+        !code.is_empty()
+            && !code.starts_with('_')
+            && !code.ends_with('_')
+            && !code.contains("__")
+            && code.bytes().all(|b| b.is_ascii_lowercase() || b == b'_')
+    }
+
+    #[test]
+    fn test_error_codes_are_unique_snake_case_and_documented() {
+        let mut seen = HashSet::new();
+        for e in every_variant() {
+            let code = e.code();
+            assert!(is_snake_case(code), "{} is not snake_case", code);
+            assert!(
+                seen.insert(code),
+                "{} is used by more than one variant",
+                code
+            );
+
+            let documented = ERROR_CODES.iter().find(|c| c.code == code);
+            let documented = documented.unwrap_or_else(|| panic!("{} is not in ERROR_CODES", code));
+            assert_eq!(documented.status, e.status_code().as_u16());
+        }
+        assert_eq!(seen.len(), ERROR_CODES.len());
+    }
+
+    #[actix_web::test]
+    async fn test_error_body() {
+        let resp = ApiError::RequestTimeout { timeout_ms: 50 }.error_response();
+        assert_eq!(resp.status(), 504);
+        let body = actix_web::body::to_bytes(resp.into_body()).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], codes::REQUEST_TIMEOUT);
+        assert_eq!(body["message"], "request timed out after 50ms");
+        assert_eq!(body["timeout_ms"], 50);
+    }
+}
@@ -0,0 +1,101 @@
+//! Runtime feature flags, seeded from `FEATURE_FLAGS` and changeable without
+//! a redeploy through `PUT /debug/flags`. Changes last until the process
+//! exits. Handlers ask [`FeatureFlags::is_enabled`]; unknown flags are off.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+
+use actix_web::{get, put, web, HttpResponse, Responder};
+
+use crate::error::ApiError;
+
+#[derive(Debug, Clone, Default)]
+pub struct FeatureFlags(Arc<RwLock<HashMap<String, bool>>>);
+
+impl FeatureFlags {
+    pub fn new(initial: HashMap<String, bool>) -> FeatureFlags {
+        FeatureFlags(Arc::new(RwLock::new(initial)))
+    }
+
+    // The query API for handlers; nothing in this app is flagged yet.
+    #[allow(dead_code)]
+    pub fn is_enabled(&self, name: &str) -> bool {
+        let flags = self.0.read().unwrap_or_else(|e| e.into_inner());
+        flags.get(name).copied().unwrap_or(false)
+    }
+
+    fn update(&self, changes: HashMap<String, bool>) {
+        let mut flags = self.0.write().unwrap_or_else(|e| e.into_inner());
+        for (name, enabled) in changes {
+            if flags.insert(name.clone(), enabled) != Some(enabled) {
+                log::info!("Feature flag {} set to {}", name, enabled);
+            }
+        }
+    }
+
+    fn snapshot(&self) -> BTreeMap<String, bool> {
+        let flags = self.0.read().unwrap_or_else(|e| e.into_inner());
+        flags.iter().map(|(name, on)| (name.clone(), *on)).collect()
+    }
+}
+
+#[get("/debug/flags")]
+pub async fn get_flags(flags: web::Data<FeatureFlags>) -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({ "flags": flags.snapshot() }))
+}
+
+/// Sets the flags named in a `{"name": bool}` body, leaving the rest alone,
+/// and returns them all.
+#[put("/debug/flags")]
+pub async fn put_flags(
+    flags: web::Data<FeatureFlags>,
+    body: web::Bytes,
+) -> Result<HttpResponse, ApiError> {
+    let changes: HashMap<String, bool> =
+        serde_json::from_slice(&body).map_err(|e| ApiError::InvalidJson {
+            reason: e.to_string(),
+        })?;
+    flags.update(changes);
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "flags": flags.snapshot() })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+    use serde_json::{json, Value};
+
+    #[actix_web::test]
+    async fn test_flags_read_and_update() {
+        let flags = FeatureFlags::new(HashMap::from([("beta".to_string(), true)]));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(flags.clone()))
+                .service(get_flags)
+                .service(put_flags),
+        )
+        .await;
+        assert!(flags.is_enabled("beta"));
+        assert!(!flags.is_enabled("unknown"));
+
+        let req = test::TestRequest::put()
+            .uri("/debug/flags")
+            .set_json(json!({"beta": false, "new_cache": true}))
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["flags"], json!({"beta": false, "new_cache": true}));
+        assert!(flags.is_enabled("new_cache") && !flags.is_enabled("beta"));
+
+        let req = test::TestRequest::get().uri("/debug/flags").to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["flags"]["new_cache"], true);
+
+        let req = test::TestRequest::put()
+            .uri("/debug/flags")
+            .set_payload(r#"{"beta": "yes"}"#)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+        assert!(!flags.is_enabled("beta"));
+    }
+}
@@ -0,0 +1,178 @@
+//! The standard gRPC health protocol (`grpc.health.v1.Health`): a client
+//! used to probe a downstream gRPC service at `GRPC_HEALTH_ADDR`, and, with
+//! `ENABLE_GRPC_HEALTH`, a server on `GRPC_PORT` so other services can probe
+//! us. The server reports `SERVING` exactly when `/health/ready` would be 200.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use actix_web::web;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::Endpoint;
+pub use tonic_health::pb::health_check_response::ServingStatus;
+use tonic_health::pb::health_client::HealthClient;
+use tonic_health::pb::HealthCheckRequest;
+
+use crate::checks::{self, HealthChecks};
+use crate::drain::Draining;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Calls `Check` for `service` (empty for the server as a whole) and returns
+/// the reported serving status.
+pub async fn check(addr: &str, service: &str) -> Result<ServingStatus, String> {
+    let channel = Endpoint::from_shared(addr.to_string())
+        .map_err(|e| format!("invalid GRPC_HEALTH_ADDR '{}': {}", addr, e))?
+        .connect_timeout(TIMEOUT)
+        .timeout(TIMEOUT)
+        .connect()
+        .await
+        .map_err(|e| format!("failed to connect: {}", e))?;
+
+    let response = HealthClient::new(channel)
+        .check(HealthCheckRequest {
+            service: service.to_string(),
+        })
+        .await
+        .map_err(|status| {
+            format!(
+                "health check failed: {:?} {}",
+                status.code(),
+                status.message()
+            )
+        })?;
+
+    Ok(ServingStatus::try_from(response.into_inner().status).unwrap_or(ServingStatus::Unknown))
+}
+
+/// A running health server; [`Server::close`] stops it.
+pub struct Server {
+    shutdown: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl Server {
+    pub async fn close(self) {
+        let _ = self.shutdown.send(());
+        let _ = self.task.await;
+    }
+}
+
+/// Binds `addr` and serves the health service on the current runtime,
+/// re-evaluating readiness every `interval`. Serving status is reported for
+/// the server as a whole (the empty service name).
+pub fn serve(
+    addr: SocketAddr,
+    checks: web::Data<HealthChecks>,
+    draining: web::Data<Draining>,
+    interval: Duration,
+) -> std::io::Result<Server> {
+    let incoming = TcpIncoming::bind(addr)?;
+    log::info!(
+        "Serving gRPC health on {}",
+        incoming.local_addr().unwrap_or(addr)
+    );
+    let (shutdown, closing) = oneshot::channel();
+
+    let task = actix_web::rt::spawn(async move {
+        let (reporter, service) = tonic_health::server::health_reporter();
+        reporter
+            .set_service_status("", tonic_health::ServingStatus::NotServing)
+            .await;
+
+        let update = async {
+            loop {
+                let (ready, _) = checks::readiness(&checks, Some(&draining)).await;
+                let status = if ready {
+                    tonic_health::ServingStatus::Serving
+                } else {
+                    tonic_health::ServingStatus::NotServing
+                };
+                reporter.set_service_status("", status).await;
+                tokio::time::sleep(interval).await;
+            }
+        };
+        let server = tonic::transport::Server::builder()
+            .add_service(service)
+            .serve_with_incoming_shutdown(incoming, async {
+                let _ = closing.await;
+            });
+
+        tokio::select! {
+            result = server => {
+                if let Err(e) = result {
+                    log::error!("gRPC health server failed: {}", e);
+                }
+            }
+            _ = update => {}
+        }
+    });
+    Ok(Server { shutdown, task })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checks::Check;
+    use crate::config::Config;
+    use crate::vault;
+
+    #[actix_web::test]
+    async fn test_server_follows_readiness() {
+        // Vault is critical and unreachable here, so readiness fails.
+        for (enabled, expected, bind) in [
+            (
+                vec![Check::TlsExpiry],
+                ServingStatus::Serving,
+                "127.0.0.1:0",
+            ),
+            (vec![Check::Vault], ServingStatus::NotServing, "127.0.0.1:0"),
+            (vec![Check::TlsExpiry], ServingStatus::Serving, "[::1]:0"),
+        ] {
+            let config = Config {
+                enabled_checks: enabled,
+                vault_addr: "http://127.0.0.1:1".to_string(),
+                ..Config::default()
+            };
+            let in_flight = prometheus::IntGaugeVec::new(
+                prometheus::Opts::new("in_flight", "test"),
+                &["dependency"],
+            )
+            .unwrap();
+            let checks = HealthChecks::new(
+                web::Data::new(config),
+                web::Data::new(vault::VaultToken::new(None, None)),
+                reqwest::Client::new(),
+                &in_flight,
+            )
+            .unwrap();
+
+            // Pick a free port, then let the server bind it.
+            let addr = std::net::TcpListener::bind(bind)
+                .unwrap()
+                .local_addr()
+                .unwrap();
+            let server = serve(
+                addr,
+                web::Data::new(checks),
+                web::Data::new(Draining::default()),
+                Duration::from_millis(20),
+            )
+            .unwrap();
+
+            let target = format!("http://{}", addr);
+            let mut status = check(&target, "").await;
+            for _ in 0..50 {
+                if status.as_ref().ok() == Some(&expected) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                status = check(&target, "").await;
+            }
+            assert_eq!(status, Ok(expected));
+            server.close().await;
+        }
+    }
+}
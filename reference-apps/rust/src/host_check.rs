@@ -0,0 +1,144 @@
+//! Rejects requests whose `Host` is not in `ALLOWED_HOSTS` with 421
+//! Misdirected Request, as defense-in-depth against Host-header attacks.
+//! The check is off while the list is empty.
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::HOST;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, ResponseError};
+
+use crate::config::Config;
+use crate::error::ApiError;
+
+/// Whether `host` (as sent, possibly with a port) matches an allowed entry.
+/// Entries without a port match any port.
+fn is_allowed(host: &str, allowed: &[String]) -> bool {
+    let host = host.trim().to_ascii_lowercase();
+    let hostname = match host.rsplit_once(':') {
+        Some((name, port)) if !name.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => host.as_str(),
+    };
+    allowed
+        .iter()
+        .any(|entry| *entry == host || *entry == hostname)
+}
+
+/// Whether `path` is one of `exempt_prefixes` or below one, on whole segments.
+pub fn is_exempt(path: &str, exempt_prefixes: &[String]) -> bool {
+    exempt_prefixes.iter().any(|prefix| {
+        path == prefix || path.starts_with(&format!("{}/", prefix.trim_end_matches('/')))
+    })
+}
+
+pub async fn check_host(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    if let Some(config) = req.app_data::<web::Data<Config>>() {
+        if !config.allowed_hosts.is_empty()
+            && !is_exempt(req.path(), &config.host_check_exempt_paths)
+        {
+            let host = req
+                .headers()
+                .get(HOST)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from)
+                .or_else(|| req.uri().authority().map(|a| a.to_string()));
+
+            if !host.is_some_and(|h| is_allowed(&h, &config.allowed_hosts)) {
+                return Ok(req.into_response(ApiError::MisdirectedRequest.error_response()));
+            }
+        }
+    }
+
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_boxed_body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{self};
+    use actix_web::{App, HttpResponse};
+
+    async fn ok() -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    fn config() -> Config {
+        Config {
+            allowed_hosts: vec!["api.dev.local".to_string(), "localhost:8004".to_string()],
+            host_check_exempt_paths: vec!["/health".to_string()],
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        let allowed = config().allowed_hosts;
+        assert!(is_allowed("api.dev.local", &allowed));
+        assert!(is_allowed("API.dev.local:8443", &allowed));
+        assert!(is_allowed("localhost:8004", &allowed));
+        assert!(!is_allowed("localhost:9000", &allowed));
+        assert!(!is_allowed("evil.example", &allowed));
+    }
+
+    #[actix_web::test]
+    async fn test_host_check_middleware() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(config()))
+                .wrap(from_fn(check_host))
+                .route("/", web::get().to(ok))
+                .route("/health/", web::get().to(ok)),
+        )
+        .await;
+
+        let allowed = test::TestRequest::get()
+            .uri("/")
+            .insert_header(("Host", "api.dev.local"))
+            .to_request();
+        assert!(test::call_service(&app, allowed)
+            .await
+            .status()
+            .is_success());
+
+        let disallowed = test::TestRequest::get()
+            .uri("/")
+            .insert_header(("Host", "evil.example"))
+            .to_request();
+        let resp = test::call_service(&app, disallowed).await;
+        assert_eq!(resp.status(), 421);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "misdirected_request");
+
+        let missing = test::TestRequest::get().uri("/").to_request();
+        assert_eq!(test::call_service(&app, missing).await.status(), 421);
+
+        let exempt = test::TestRequest::get()
+            .uri("/health/")
+            .insert_header(("Host", "evil.example"))
+            .to_request();
+        assert!(test::call_service(&app, exempt).await.status().is_success());
+    }
+
+    #[actix_web::test]
+    async fn test_empty_allowlist_disables_check() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(Config::default()))
+                .wrap(from_fn(check_host))
+                .route("/", web::get().to(ok)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .insert_header(("Host", "anything.example"))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+    }
+}
@@ -0,0 +1,241 @@
+//! Generic HTTP endpoint checks (`HTTP_CHECKS`), for downstreams with a plain
+//! HTTP health route.
+//!
+//! Entries are comma-separated `name=url` with optional `|`-separated
+//! extras: `Header:Value` request headers (so endpoints behind auth can be
+//! probed) and `expect=<status>` for an exact expected status instead of
+//! any 2xx, e.g.
+//! `billing=http://billing:8080/health|Authorization:Bearer xyz|expect=204`.
+//! Header values are never logged or reported.
+
+use std::time::Duration;
+
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::{StatusCode, Url};
+use serde::Serialize;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone)]
+pub struct HttpCheck {
+    pub name: String,
+    pub url: Url,
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+    pub expected_status: Option<StatusCode>,
+}
+
+impl std::fmt::Debug for HttpCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let headers: Vec<&str> = self.headers.iter().map(|(name, _)| name.as_str()).collect();
+        f.debug_struct("HttpCheck")
+            .field("name", &self.name)
+            .field("url", &self.url.as_str())
+            .field("headers", &headers)
+            .field("expected_status", &self.expected_status)
+            .finish()
+    }
+}
+
+impl std::str::FromStr for HttpCheck {
+    type Err = String;
+
+    fn from_str(entry: &str) -> Result<Self, Self::Err> {
+        let mut parts = entry.split('|');
+        let target = parts.next().unwrap_or_default();
+        let (name, url) = target
+            .split_once('=')
+            .ok_or_else(|| format!("'{}' is not name=url", target.trim()))?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("empty check name".to_string());
+        }
+        let url = Url::parse(url.trim()).map_err(|e| format!("{}: invalid URL: {}", name, e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("{}: URL must be http or https", name));
+        }
+
+        let mut check = HttpCheck {
+            name: name.to_string(),
+            url,
+            headers: Vec::new(),
+            expected_status: None,
+        };
+        for part in parts.map(str::trim).filter(|p| !p.is_empty()) {
+            if let Some(status) = part.strip_prefix("expect=") {
+                let status = status
+                    .trim()
+                    .parse::<u16>()
+                    .ok()
+                    .and_then(|s| StatusCode::from_u16(s).ok())
+                    .ok_or_else(|| format!("{}: invalid expected status '{}'", name, status))?;
+                check.expected_status = Some(status);
+            } else if let Some((header, value)) = part.split_once(':') {
+                // The value may be a credential, so it stays out of errors.
+                let header = HeaderName::from_bytes(header.trim().as_bytes())
+                    .map_err(|_| format!("{}: invalid header name '{}'", name, header.trim()))?;
+                let mut value = HeaderValue::from_str(value.trim())
+                    .map_err(|_| format!("{}: invalid value for header '{}'", name, header))?;
+                value.set_sensitive(true);
+                check.headers.push((header, value));
+            } else {
+                return Err(format!(
+                    "{}: '{}' is neither Header:Value nor expect=<status>",
+                    name, part
+                ));
+            }
+        }
+        Ok(check)
+    }
+}
+
+impl HttpCheck {
+    fn accepts(&self, status: StatusCode) -> bool {
+        match self.expected_status {
+            Some(expected) => status == expected,
+            None => status.is_success(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct EndpointHealth {
+    pub name: String,
+    pub url: String,
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl EndpointHealth {
+    pub fn is_healthy(&self) -> bool {
+        self.status == "healthy"
+    }
+}
+
+pub async fn probe(http: &reqwest::Client, check: &HttpCheck) -> EndpointHealth {
+    probe_within(http, check, TIMEOUT).await
+}
+
+async fn probe_within(
+    http: &reqwest::Client,
+    check: &HttpCheck,
+    timeout: Duration,
+) -> EndpointHealth {
+    let mut request = http.get(check.url.clone()).timeout(timeout);
+    for (name, value) in &check.headers {
+        request = request.header(name, value);
+    }
+    let (http_status, error) = match request.send().await {
+        Ok(resp) if check.accepts(resp.status()) => (Some(resp.status()), None),
+        Ok(resp) => (
+            Some(resp.status()),
+            Some(format!("unexpected HTTP {}", resp.status())),
+        ),
+        Err(e) => (None, Some(format!("request failed: {}", e))),
+    };
+    EndpointHealth {
+        name: check.name.clone(),
+        url: check.url.to_string(),
+        status: if error.is_none() {
+            "healthy"
+        } else {
+            "unhealthy"
+        },
+        http_status: http_status.map(|s| s.as_u16()),
+        expected_status: check.expected_status.map(|s| s.as_u16()),
+        error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_parse_entries() {
+        let check: HttpCheck =
+            "billing=http://billing:8080/health|Authorization:Bearer xyz|expect=204"
+                .parse()
+                .unwrap();
+        assert_eq!(check.name, "billing");
+        assert_eq!(check.url.as_str(), "http://billing:8080/health");
+        assert_eq!(check.headers.len(), 1);
+        assert_eq!(check.headers[0].0, "authorization");
+        assert_eq!(check.expected_status, Some(StatusCode::NO_CONTENT));
+        assert!(check.accepts(StatusCode::NO_CONTENT));
+        assert!(!check.accepts(StatusCode::OK));
+        assert!(!format!("{:?}", check).contains("xyz"));
+
+        let plain: HttpCheck = "search=https://search/health".parse().unwrap();
+        assert!(plain.accepts(StatusCode::OK) && !plain.accepts(StatusCode::UNAUTHORIZED));
+
+        for bad in [
+            "no-url",
+            "=http://x",
+            "x=not a url",
+            "x=ftp://x",
+            "x=http://x|expect=abc",
+            "x=http://x|junk",
+            "x=http://x|Bad Header:v",
+        ] {
+            assert!(
+                bad.parse::<HttpCheck>().is_err(),
+                "{} should be rejected",
+                bad
+            );
+        }
+        let err = "x=http://x|Authorization:Bearer\nxyz"
+            .parse::<HttpCheck>()
+            .unwrap_err();
+        assert!(!err.contains("xyz"));
+    }
+
+    #[actix_web::test]
+    async fn test_probe_against_mock_server() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/up"))
+            .and(header("x-probe", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("{not json"))
+            .mount(&server)
+            .await;
+        Mock::given(path("/down"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+        Mock::given(path("/slow"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(2)))
+            .mount(&server)
+            .await;
+
+        let http = reqwest::Client::new();
+        let check = |entry: String| entry.parse::<HttpCheck>().unwrap();
+        let short = Duration::from_millis(200);
+
+        // The body isn't inspected, so a malformed one still passes.
+        let up = check(format!("up={}/up|X-Probe:1", server.uri()));
+        let result = probe_within(&http, &up, short).await;
+        assert!(result.is_healthy(), "{:?}", result);
+        assert_eq!(result.http_status, Some(200));
+
+        let down = check(format!("down={}/down", server.uri()));
+        let result = probe_within(&http, &down, short).await;
+        assert!(!result.is_healthy());
+        assert_eq!(result.http_status, Some(503));
+        assert_eq!(
+            result.error.as_deref(),
+            Some("unexpected HTTP 503 Service Unavailable")
+        );
+
+        let slow = check(format!("slow={}/slow", server.uri()));
+        let result = probe_within(&http, &slow, short).await;
+        assert_eq!(result.http_status, None);
+        assert!(result.error.unwrap().starts_with("request failed"));
+    }
+}
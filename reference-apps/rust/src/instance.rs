@@ -0,0 +1,84 @@
+//! Instance identity: every response carries `X-Served-By` with this
+//! process's ID (`INSTANCE_ID`, or a random UUID per process), and the
+//! access log records it, so a request can be pinned to the replica that
+//! served it.
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+
+use crate::config::Config;
+
+pub const SERVED_BY: HeaderName = HeaderName::from_static("x-served-by");
+
+/// A random (version 4) UUID. Only needs to be unique across replicas, not
+/// unguessable.
+pub fn generate_id() -> String {
+    let hi = (fastrand::u64(..) & !0xf000) | 0x4000;
+    let lo = (fastrand::u64(..) & !(0b11 << 62)) | (0b10 << 62);
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        hi >> 32,
+        (hi >> 16) & 0xffff,
+        hi & 0xffff,
+        lo >> 48,
+        lo & 0xffff_ffff_ffff
+    )
+}
+
+pub async fn add_served_by(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let value = req
+        .app_data::<web::Data<Config>>()
+        .and_then(|config| HeaderValue::from_str(&config.instance_id).ok())
+        .filter(|value| !value.is_empty());
+    let mut resp = next.call(req).await?.map_into_boxed_body();
+    if let Some(value) = value {
+        resp.headers_mut().insert(SERVED_BY, value);
+    }
+    Ok(resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{self};
+    use actix_web::{App, HttpResponse};
+
+    #[test]
+    fn test_generated_ids_are_v4_uuids() {
+        let id = generate_id();
+        let groups: Vec<&str> = id.split('-').collect();
+        assert_eq!(
+            groups.iter().map(|g| g.len()).collect::<Vec<_>>(),
+            [8, 4, 4, 4, 12]
+        );
+        assert!(groups[2].starts_with('4'));
+        assert!(matches!(groups[3].as_bytes()[0], b'8' | b'9' | b'a' | b'b'));
+        assert_ne!(id, generate_id());
+    }
+
+    #[actix_web::test]
+    async fn test_served_by_header() {
+        let config = Config {
+            instance_id: "pod-7".to_string(),
+            ..Config::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(config))
+                .wrap(from_fn(add_served_by))
+                .route("/", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.headers().get(SERVED_BY).unwrap(), "pod-7");
+    }
+}
@@ -0,0 +1,264 @@
+//! Response field naming. Handlers serialize snake_case; with
+//! `JSON_FIELD_CASE=camel` field names in a JSON response body are rewritten
+//! to camelCase on the way out, so the frontend gets `schemaVersion` without
+//! each struct carrying its own `#[serde(rename_all)]`. Keys that are data
+//! rather than field names are left alone: those of the maps in
+//! [`DATA_MAPS`] (check, flag and secret key names) and everything under
+//! [`VERBATIM`] (the body `POST /echo` sends back).
+//!
+//! The same pass pretty-prints JSON bodies for `?pretty` (or
+//! `?pretty=true`) requests, or for every request with
+//! `JSON_PRETTY_DEFAULT=true`; `?pretty=false` asks for compact output
+//! either way.
+//!
+//! Errors raised by middleware rather than handlers (request timeouts) are
+//! rendered after this runs and keep snake_case.
+
+use std::str::FromStr;
+
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::CONTENT_TYPE;
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use serde_json::{Map, Value};
+
+use crate::config::{parse_bool, Config};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FieldCase {
+    #[default]
+    Snake,
+    Camel,
+}
+
+impl FromStr for FieldCase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "snake" => Ok(FieldCase::Snake),
+            "camel" => Ok(FieldCase::Camel),
+            other => Err(format!("unknown JSON field case '{}'", other)),
+        }
+    }
+}
+
+fn to_camel_case(key: &str) -> String {
+    let mut out = String::with_capacity(key.len());
+    let mut upper = false;
+    for c in key.chars() {
+        if c == '_' && !out.is_empty() {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Fields holding a map keyed by name (`/health/all` `services`,
+/// `/health/ready` `checks`, `unwrap.keys`, `/debug/flags`). The names are
+/// kept; the values are still rewritten.
+const DATA_MAPS: &[&str] = &["services", "checks", "keys", "flags"];
+
+/// Fields holding client-supplied JSON, passed through untouched.
+const VERBATIM: &[&str] = &["body"];
+
+fn camelize(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| {
+                    let v = if VERBATIM.contains(&k.as_str()) {
+                        v
+                    } else if DATA_MAPS.contains(&k.as_str()) {
+                        camelize_values(v)
+                    } else {
+                        camelize(v)
+                    };
+                    (to_camel_case(&k), v)
+                })
+                .collect::<Map<_, _>>(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(camelize).collect()),
+        other => other,
+    }
+}
+
+/// Rewrites within a map's values, keeping its own keys.
+fn camelize_values(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            Value::Object(map.into_iter().map(|(k, v)| (k, camelize(v))).collect())
+        }
+        other => camelize(other),
+    }
+}
+
+/// `?pretty`, `?pretty=true` or `?pretty=false`, if given.
+fn pretty_param(query: &str) -> Option<bool> {
+    query
+        .split('&')
+        .find_map(|pair| match pair.split_once('=') {
+            Some(("pretty", value)) => Some(parse_bool(value)),
+            None if pair == "pretty" => Some(true),
+            _ => None,
+        })
+}
+
+pub async fn format_json(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let config = req.app_data::<web::Data<Config>>();
+    let camel = config.is_some_and(|config| config.json_field_case == FieldCase::Camel);
+    let pretty = pretty_param(req.query_string())
+        .unwrap_or_else(|| config.is_some_and(|config| config.json_pretty_default));
+    let resp = next.call(req).await?.map_into_boxed_body();
+
+    let is_json = resp
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if !(camel || pretty) || !is_json {
+        return Ok(resp);
+    }
+
+    let (req, resp) = resp.into_parts();
+    let (resp, body) = resp.into_parts();
+    let bytes = body::to_bytes(body)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let bytes = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(value) => {
+            let value = if camel { camelize(value) } else { value };
+            let encoded = if pretty {
+                serde_json::to_vec_pretty(&value)
+            } else {
+                serde_json::to_vec(&value)
+            };
+            encoded.unwrap_or_else(|_| bytes.to_vec())
+        }
+        Err(_) => bytes.to_vec(),
+    };
+    Ok(ServiceResponse::new(
+        req,
+        resp.set_body(BoxBody::new(bytes)),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{self};
+    use actix_web::{App, HttpResponse};
+
+    async fn info() -> HttpResponse {
+        HttpResponse::Ok().json(serde_json::json!({
+            "schema_version": 1,
+            "services": { "tls_expiry": { "days_until_expiry": 3 } },
+            "tls": { "hosts": [{ "days_until_expiry": 3 }] }
+        }))
+    }
+
+    async fn echo(body: web::Json<Value>) -> HttpResponse {
+        HttpResponse::Ok().json(serde_json::json!({
+            "body": body.into_inner(),
+            "received_bytes": 2
+        }))
+    }
+
+    #[test]
+    fn test_to_camel_case() {
+        assert_eq!(to_camel_case("schema_version"), "schemaVersion");
+        assert_eq!(to_camel_case("days_until_expiry"), "daysUntilExpiry");
+        assert_eq!(to_camel_case("status"), "status");
+        assert_eq!(to_camel_case("_private"), "_private");
+    }
+
+    #[actix_web::test]
+    async fn test_field_case() {
+        for (case, key, nested) in [
+            (FieldCase::Snake, "schema_version", "days_until_expiry"),
+            (FieldCase::Camel, "schemaVersion", "daysUntilExpiry"),
+        ] {
+            let config = Config {
+                json_field_case: case,
+                ..Config::default()
+            };
+            let app = test::init_service(
+                App::new()
+                    .app_data(web::Data::new(config))
+                    .wrap(from_fn(format_json))
+                    .route("/", web::get().to(info)),
+            )
+            .await;
+
+            let req = test::TestRequest::get().uri("/").to_request();
+            let body: Value = test::call_and_read_body_json(&app, req).await;
+            assert_eq!(body[key], 1);
+            assert_eq!(body["tls"]["hosts"][0][nested], 3);
+            // Check names are data, but their results' fields aren't.
+            assert_eq!(body["services"]["tls_expiry"][nested], 3);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_camel_case_keeps_echoed_body() {
+        let config = Config {
+            json_field_case: FieldCase::Camel,
+            ..Config::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(config))
+                .wrap(from_fn(format_json))
+                .route("/echo", web::post().to(echo)),
+        )
+        .await;
+
+        let sent = serde_json::json!({"order_id": 7, "line_items": [{"unit_price": 1}]});
+        let req = test::TestRequest::post()
+            .uri("/echo")
+            .set_json(&sent)
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["body"], sent);
+        assert_eq!(body["receivedBytes"], 2);
+    }
+
+    #[actix_web::test]
+    async fn test_pretty_printing() {
+        for (pretty_default, uri, pretty) in [
+            (false, "/", false),
+            (false, "/?pretty", true),
+            (false, "/?a=1&pretty=true", true),
+            (true, "/", true),
+            (true, "/?pretty=false", false),
+        ] {
+            let config = Config {
+                json_pretty_default: pretty_default,
+                ..Config::default()
+            };
+            let app = test::init_service(
+                App::new()
+                    .app_data(web::Data::new(config))
+                    .wrap(from_fn(format_json))
+                    .route("/", web::get().to(info)),
+            )
+            .await;
+
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let body = test::call_and_read_body(&app, req).await;
+            assert_eq!(body.contains(&b'\n'), pretty, "{}", uri);
+            let body: Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["schema_version"], 1);
+        }
+    }
+}
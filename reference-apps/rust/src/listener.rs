@@ -0,0 +1,33 @@
+//! Binding the HTTP listener with explicit socket options, rather than
+//! relying on the server's defaults, so what's logged is what's applied.
+
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+/// Matches actix's own default listen backlog.
+const BACKLOG: i32 = 1024;
+
+/// Binds `addr`, with `SO_REUSEADDR` when `reuse_address` is set so a quick
+/// restart can rebind while old connections sit in TIME_WAIT.
+pub fn bind(addr: SocketAddr, reuse_address: bool) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_reuse_address(reuse_address)?;
+    socket.bind(&addr.into())?;
+    socket.listen(BACKLOG)?;
+    log::info!("Socket options: SO_REUSEADDR={}", socket.reuse_address()?);
+    Ok(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bind_sets_reuse_address() {
+        let listener = bind("127.0.0.1:0".parse().unwrap(), true).unwrap();
+        let socket = Socket::from(listener);
+        assert!(socket.reuse_address().unwrap());
+    }
+}
@@ -0,0 +1,113 @@
+//! Logger setup, plus `GET /debug/logging` to confirm what logging is
+//! doing: the filter in effect and how many records have been emitted at
+//! each level since startup.
+//!
+//! env_logger does the filtering and formatting; it's wrapped in a
+//! [`log::Log`] that counts the records it lets through.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+use actix_web::{get, HttpResponse, Responder};
+use log::{Level, Log, Metadata, Record};
+use serde_json::{Map, Value};
+
+const DEFAULT_FILTER: &str = "info";
+
+static LOGGER: OnceLock<&'static CountingLogger> = OnceLock::new();
+
+struct CountingLogger {
+    inner: env_logger::Logger,
+    filter: String,
+    /// Emitted records, indexed by `Level as usize - 1`.
+    counts: [AtomicU64; 5],
+}
+
+impl CountingLogger {
+    fn new(filter: &str) -> CountingLogger {
+        let mut builder = env_logger::Builder::new();
+        builder.parse_filters(filter);
+        if let Ok(style) = std::env::var("RUST_LOG_STYLE") {
+            builder.parse_write_style(&style);
+        }
+        CountingLogger {
+            inner: builder.build(),
+            filter: filter.to_string(),
+            counts: Default::default(),
+        }
+    }
+
+    fn counts(&self) -> Map<String, Value> {
+        Level::iter()
+            .map(|level| {
+                let count = self.counts[level as usize - 1].load(Ordering::Relaxed);
+                (level.as_str().to_ascii_lowercase(), count.into())
+            })
+            .collect()
+    }
+}
+
+impl Log for CountingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.inner.matches(record) {
+            self.counts[record.level() as usize - 1].fetch_add(1, Ordering::Relaxed);
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Installs the logger, filtered by `RUST_LOG` (default `info`).
+pub fn init() {
+    let filter = std::env::var("RUST_LOG").unwrap_or_else(|_| DEFAULT_FILTER.to_string());
+    let logger: &'static CountingLogger = Box::leak(Box::new(CountingLogger::new(&filter)));
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(logger.inner.filter());
+        let _ = LOGGER.set(logger);
+    }
+}
+
+#[get("/debug/logging")]
+pub async fn debug_logging() -> impl Responder {
+    let Some(logger) = LOGGER.get() else {
+        return HttpResponse::Ok().json(serde_json::json!({ "installed": false }));
+    };
+    HttpResponse::Ok().json(serde_json::json!({
+        "installed": true,
+        // env_logger's text format is the only one.
+        "format": "text",
+        "filter": logger.filter,
+        "max_level": log::max_level().as_str().to_ascii_lowercase(),
+        "records": logger.counts(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_only_emitted_records() {
+        let logger = CountingLogger::new("warn,rust_api::noisy=error");
+        let emit = |level: Level, target: &str| {
+            logger.log(&Record::builder().level(level).target(target).build());
+        };
+        emit(Level::Error, "rust_api");
+        emit(Level::Warn, "rust_api");
+        emit(Level::Warn, "rust_api::noisy");
+        emit(Level::Info, "rust_api");
+
+        let counts = logger.counts();
+        assert_eq!(counts["error"], 1);
+        assert_eq!(counts["warn"], 1);
+        assert_eq!(counts["info"], 0);
+        assert_eq!(counts.len(), 5);
+    }
+}
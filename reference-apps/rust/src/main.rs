@@ -1,7 +1,51 @@
-use actix_web::{get, App, HttpResponse, HttpServer, Responder};
-use actix_cors::Cors;
+use actix_web::http::header::{self, EntityTag, IfNoneMatch};
+use actix_web::middleware::{from_fn, Condition, Logger, NormalizePath};
+use actix_web::{get, web, App, HttpMessage, HttpRequest, HttpResponse, HttpServer, Responder};
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
 use std::env;
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+
+mod cache_control;
+mod chaos;
+mod checks;
+mod client_ip;
+mod config;
+mod cors;
+mod drain;
+mod echo;
+mod error;
+mod flags;
+mod grpc_health;
+mod host_check;
+mod http_check;
+mod http_client;
+mod instance;
+mod json_case;
+mod listener;
+mod logging;
+mod maintenance;
+mod memory;
+mod metrics;
+mod pg_notify;
+mod pushgateway;
+mod recorder;
+mod registry;
+mod routes;
+mod signature;
+mod socket_activation;
+mod timeout;
+mod timestamp;
+mod timings;
+mod tls_expiry;
+mod trailing_slash;
+mod uptime;
+mod vault;
+
+use checks::HEALTH_SCHEMA_VERSION;
 
 #[derive(Serialize, Deserialize)]
 struct ApiInfo {
@@ -12,13 +56,30 @@ struct ApiInfo {
     description: String,
 }
 
+#[derive(Serialize, Deserialize)]
+struct BuildInfo {
+    name: String,
+    version: String,
+    git_sha: String,
+    profile: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct UptimeResponse {
+    started_at: String,
+    uptime_seconds: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    restart_count: Option<u64>,
+}
+
 #[derive(Serialize, Deserialize)]
 struct HealthResponse {
+    schema_version: u32,
     status: String,
     timestamp: String,
 }
 
-#[get("/")]
+/// The API info, served at `/` and as each version's index (`/v1`).
 async fn root() -> impl Responder {
     let info = ApiInfo {
         name: "DevStack Core Rust Reference API".to_string(),
@@ -30,109 +91,641 @@ async fn root() -> impl Responder {
     HttpResponse::Ok().json(info)
 }
 
-#[get("/health/")]
+/// `Cache-Control` max-age for `/info/build`. The body can't change while the
+/// process runs, and the ETag catches a redeploy once the cache expires.
+const BUILD_INFO_MAX_AGE: u32 = 86_400;
+
+/// `Cache-Control` max-age for `/info/uptime`, which ticks every second but
+/// gets polled far more often than that.
+const UPTIME_MAX_AGE: u32 = 1;
+
+/// Build info and its ETag, computed once: neither can change for the
+/// lifetime of the process.
+fn build_info() -> &'static (BuildInfo, EntityTag) {
+    static BUILD_INFO: OnceLock<(BuildInfo, EntityTag)> = OnceLock::new();
+    BUILD_INFO.get_or_init(|| {
+        let info = BuildInfo {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_sha: option_env!("GIT_SHA").unwrap_or("unknown").to_string(),
+            profile: if cfg!(debug_assertions) {
+                "debug"
+            } else {
+                "release"
+            }
+            .to_string(),
+        };
+        let mut hasher = DefaultHasher::new();
+        info.version.hash(&mut hasher);
+        info.git_sha.hash(&mut hasher);
+        info.profile.hash(&mut hasher);
+        let etag = EntityTag::new_strong(format!("{:016x}", hasher.finish()));
+        (info, etag)
+    })
+}
+
+#[get("/info/build")]
+async fn info_build(req: HttpRequest) -> impl Responder {
+    let (info, etag) = build_info();
+
+    let not_modified = match req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(etag)),
+        None => false,
+    };
+
+    let cache_control = header::CacheControl(vec![
+        header::CacheDirective::Public,
+        header::CacheDirective::MaxAge(BUILD_INFO_MAX_AGE),
+    ]);
+
+    if not_modified {
+        return HttpResponse::NotModified()
+            .insert_header(header::ETag(etag.clone()))
+            .insert_header(cache_control)
+            .finish();
+    }
+
+    HttpResponse::Ok()
+        .insert_header(header::ETag(etag.clone()))
+        .insert_header(cache_control)
+        .json(info)
+}
+
+#[get("/info/uptime")]
+async fn info_uptime(uptime: web::Data<uptime::Uptime>) -> impl Responder {
+    HttpResponse::Ok()
+        .insert_header(header::CacheControl(vec![
+            header::CacheDirective::Public,
+            header::CacheDirective::MaxAge(UPTIME_MAX_AGE),
+        ]))
+        .json(UptimeResponse {
+            started_at: timestamp::format(uptime.started_at),
+            uptime_seconds: uptime.uptime_seconds(),
+            restart_count: uptime.restart_count,
+        })
+}
+
+#[get("/health")]
 async fn health() -> impl Responder {
     let response = HealthResponse {
+        schema_version: HEALTH_SCHEMA_VERSION,
         status: "healthy".to_string(),
-        timestamp: chrono::Utc::now().to_rfc3339(),
+        timestamp: timestamp::now(),
     };
     HttpResponse::Ok().json(response)
 }
 
-#[get("/health/vault")]
-async fn health_vault() -> impl Responder {
-    let vault_addr = env::var("VAULT_ADDR").unwrap_or_else(|_| "http://vault:8200".to_string());
+/// Version 1 of the API, served under `/v1`. A later version gets its own
+/// `configure_v2`, registered under `/v2` next to this one.
+fn configure_v1(routes: &mut routes::Routes) {
+    routes
+        .add("GET", "", web::resource("").get(root))
+        .add("GET", "/info/build", info_build)
+        .add("GET", "/info/uptime", info_uptime)
+        .add("POST", "/echo", echo::echo);
+    configure_health(routes);
+}
 
-    match reqwest::get(format!("{}/v1/sys/health", vault_addr)).await {
-        Ok(resp) if resp.status().is_success() => {
-            HttpResponse::Ok().json(serde_json::json!({
-                "status": "healthy"
-            }))
-        }
-        _ => HttpResponse::ServiceUnavailable().json(serde_json::json!({
-            "status": "unhealthy",
-            "error": "Vault unavailable"
-        }))
+/// Health routes: part of each API version, and also served unversioned so
+/// orchestrator probes don't change with the API.
+fn configure_health(routes: &mut routes::Routes) {
+    routes
+        .add("GET", "/health", health)
+        .add("GET", "/health/all", checks::health_all)
+        .add("GET", "/health/ready", checks::health_ready)
+        .add("GET", "/health/history", checks::health_history)
+        .add("GET", "/health/check/{name}", checks::health_check_one)
+        .add("GET", "/health/vault", checks::health_vault)
+        .add("GET", "/health/tls-expiry", checks::health_tls_expiry)
+        .add("GET", "/health/grpc", checks::health_grpc);
+}
+
+/// Registers every route, recording each in `table` for `/debug/routes`.
+/// Besides the versioned API, `/` (the API info, which repo-wide scripts
+/// poll), the health routes, `/metrics` and `/debug/*` stay unversioned.
+fn register_routes(
+    cfg: &mut web::ServiceConfig,
+    table: &routes::RouteTable,
+    debug_endpoints: bool,
+    chaos_endpoints: bool,
+) {
+    let mut routes = routes::Routes::new(cfg);
+    routes
+        .scope("/v1", configure_v1)
+        .add("GET", "/", web::resource("/").get(root));
+    configure_health(&mut routes);
+    routes.add("GET", "/metrics", metrics::metrics_endpoint);
+    if debug_endpoints {
+        routes
+            .add("GET", "/debug/error-codes", error::error_codes)
+            .add("GET", "/debug/routes", routes::debug_routes)
+            .add("GET", "/debug/flags", flags::get_flags)
+            .add("GET", "/debug/logging", logging::debug_logging)
+            .add("PUT", "/debug/flags", flags::put_flags);
+    }
+    if chaos_endpoints {
+        routes.add("GET", "/debug/chaos", chaos::get_chaos);
+        routes.add("PUT", "/debug/chaos", chaos::put_chaos);
     }
+    routes.finish(table);
 }
 
-#[get("/metrics")]
-async fn metrics() -> impl Responder {
-    HttpResponse::Ok()
-        .content_type("text/plain")
-        .body("# Rust API metrics placeholder\n")
+/// Access log keyed on the resolved client IP rather than the raw peer or
+/// first `X-Forwarded-For` entry, ending with the request's time breakdown
+/// and the instance that served it.
+fn access_logger(config: config::Config) -> Logger {
+    let instance_id = config.instance_id.clone();
+    Logger::new("%{client_ip}xi \"%r\" %s %b %Ts %{timings}xo served_by=%{served_by}xi")
+        .custom_request_replace("served_by", move |_| instance_id.clone())
+        .custom_request_replace("client_ip", move |req| {
+            client_ip::from_request(req, &config)
+                .map(|ip| ip.to_string())
+                .unwrap_or_else(|| "-".to_string())
+        })
+        .custom_response_replace("timings", |res| {
+            res.response()
+                .extensions()
+                .get::<timings::Timings>()
+                .map(timings::Timings::to_log_fields)
+                .unwrap_or_else(|| "-".to_string())
+        })
+}
+
+/// Re-reads [`config::HotConfig`] whenever the process receives SIGHUP.
+#[cfg(unix)]
+fn reload_on_sighup(hot_config: web::Data<RwLock<config::HotConfig>>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    actix_web::rt::spawn(async move {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(e) => {
+                log::warn!("Config reload on SIGHUP unavailable: {}", e);
+                return;
+            }
+        };
+        while hangup.recv().await.is_some() {
+            config::HotConfig::reload_into(&hot_config, "SIGHUP");
+        }
+    });
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+    logging::init();
 
     let port = env::var("HTTP_PORT")
         .unwrap_or_else(|_| "8004".to_string())
         .parse::<u16>()
         .unwrap_or(8004);
 
-    log::info!("Starting Rust API on port {}", port);
+    let config = config::Config::from_env();
+    log::info!(
+        "Profile: {}, instance ID: {}",
+        config.profile.name(),
+        config.instance_id
+    );
+    config.check_required().map_err(std::io::Error::other)?;
+    timestamp::init(config.timestamp_tz);
+    let vault_token = web::Data::new(vault::VaultToken::new(
+        env::var("VAULT_TOKEN").ok(),
+        config.vault_token_file.clone(),
+    ));
+    vault_token
+        .clone()
+        .into_inner()
+        .watch(Duration::from_secs(config.vault_token_file_poll_secs));
+    let http = http_client::build(&config.proxy).map_err(std::io::Error::other)?;
+    let vault_http = http_client::build_vault(&config.proxy, &config.vault_tls, &http)
+        .map_err(std::io::Error::other)?;
+    vault::verify_required_secrets(&vault_http, &config, vault_token.get().as_deref())
+        .await
+        .map_err(std::io::Error::other)?;
+    let shared_config = web::Data::new(config.clone());
+    let metrics = web::Data::new(metrics::Metrics::new(&config));
+    let chaos = config.chaos_enabled.then(chaos::Chaos::default);
+    if chaos.is_some() {
+        if config.debug_endpoints {
+            log::warn!("Chaos mode enabled; faults can be injected through PUT /debug/chaos");
+        } else {
+            log::warn!("CHAOS_ENABLED is set, but /debug/chaos needs DEBUG_ENDPOINTS");
+        }
+    }
+    let mut health_checks = checks::HealthChecks::new(
+        shared_config.clone(),
+        vault_token.clone(),
+        http.clone(),
+        metrics.dependency_in_flight(),
+    )
+    .map_err(std::io::Error::other)?
+    .with_readiness_gauge(metrics.readiness_state().clone())
+    .with_vault_http(vault_http);
+    if let Some(chaos) = &chaos {
+        health_checks = health_checks.with_chaos(chaos.clone());
+    }
+    let health_checks = web::Data::new(health_checks);
+    let chaos = chaos.map(web::Data::new);
+    log::info!(
+        "Enabled health checks: {}",
+        health_checks
+            .enabled()
+            .iter()
+            .map(|check| check.name())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let uptime = web::Data::new(uptime::Uptime::start(config.restart_count_file.as_deref()));
+    if let Some(restarts) = uptime.restart_count {
+        log::info!("Restart count: {}", restarts);
+    }
+    if config.memory_ready_limit_mb.is_some() && memory::rss_bytes().is_none() {
+        log::warn!(
+            "MEMORY_READY_LIMIT_MB is set, but resident memory can't be read on this \
+             platform (no /proc/self/status); readiness won't consider memory"
+        );
+    }
+    let hot_config = web::Data::new(RwLock::new(
+        config::HotConfig::load().map_err(std::io::Error::other)?,
+    ));
+    if hot_config.read().unwrap().maintenance_mode {
+        log::warn!("Starting in maintenance mode");
+    }
+    #[cfg(unix)]
+    reload_on_sighup(hot_config.clone());
+    let pg_listener = config
+        .pg_config_notify
+        .then(|| pg_notify::spawn_listener(&config, hot_config.clone()));
+    let close_timeout = Duration::from_secs(config.dependency_close_timeout_secs);
+    let pre_stop_delay = Duration::from_secs(config.pre_stop_delay_secs);
+    let draining = web::Data::new(drain::Draining::default());
+    let app_draining = draining.clone();
+    let recorder = recorder::Recorder::from_config(&config).map(web::Data::new);
+    let route_table = web::Data::new(routes::RouteTable::default());
+    let feature_flags = web::Data::new(flags::FeatureFlags::new(config.feature_flags.clone()));
+    let seen_signatures = web::Data::new(signature::SeenSignatures::default());
+    let grpc_server = if config.enable_grpc_health {
+        Some(grpc_health::serve(
+            SocketAddr::from(([0, 0, 0, 0], config.grpc_port)),
+            health_checks.clone(),
+            draining.clone(),
+            Duration::from_secs(config.grpc_health_interval_secs),
+        )?)
+    } else {
+        None
+    };
+    let heartbeat = registry::start(
+        &config,
+        http.clone(),
+        health_checks.clone(),
+        draining.clone(),
+        port,
+    );
+    let pusher = pushgateway::start(&config, http, metrics.clone());
+    let (tcp_nodelay, reuse_address) = (config.tcp_nodelay, config.so_reuseaddr);
+    let (report_metrics, report_uptime) = (metrics.clone(), uptime.clone());
 
-    HttpServer::new(|| {
-        let cors = Cors::permissive();
+    let server = HttpServer::new(move || {
+        let cors = cors::build(&config);
+        let debug_endpoints = config.debug_endpoints;
+        let chaos_endpoints = debug_endpoints && chaos.is_some();
 
         App::new()
+            .app_data(shared_config.clone())
+            .app_data(health_checks.clone())
+            .app_data(hot_config.clone())
+            .app_data(uptime.clone())
+            .app_data(metrics.clone())
+            .app_data(vault_token.clone())
+            .app_data(app_draining.clone())
+            .app_data(seen_signatures.clone())
+            .configure(|cfg| {
+                if let Some(recorder) = &recorder {
+                    cfg.app_data(recorder.clone());
+                }
+                if let Some(chaos) = &chaos {
+                    cfg.app_data(chaos.clone());
+                }
+            })
+            .wrap(from_fn(chaos::inject_faults))
+            .wrap(from_fn(timeout::enforce_timeout))
+            .wrap(from_fn(signature::verify_signature))
+            .wrap(from_fn(maintenance::reject_during_maintenance))
+            .wrap(from_fn(host_check::check_host))
+            .wrap(from_fn(json_case::format_json))
+            .wrap(from_fn(cache_control::apply_defaults))
             .wrap(cors)
-            .service(root)
-            .service(health)
-            .service(health_vault)
-            .service(metrics)
+            .wrap(from_fn(trailing_slash::redirect_trailing_slash))
+            .wrap(Condition::new(
+                config.trailing_slash_mode == trailing_slash::TrailingSlashMode::Trim,
+                NormalizePath::trim(),
+            ))
+            .wrap(from_fn(recorder::record_requests))
+            .wrap(from_fn(metrics::track_requests))
+            .wrap(from_fn(timings::track_timings))
+            .wrap(from_fn(instance::add_served_by))
+            .wrap(access_logger(config.clone()))
+            .app_data(route_table.clone())
+            .app_data(feature_flags.clone())
+            .configure(|cfg| register_routes(cfg, &route_table, debug_endpoints, chaos_endpoints))
     })
-    .bind(("0.0.0.0", port))?
-    .run()
-    .await
+    .on_connect(metrics::stamp_connection)
+    .tcp_nodelay(tcp_nodelay);
+    log::info!("Socket options: TCP_NODELAY={}", tcp_nodelay);
+
+    let server = match socket_activation::take_listener() {
+        Some(listener) => {
+            log::info!(
+                "Starting Rust API on systemd-activated socket {}",
+                listener
+                    .local_addr()
+                    .map(|addr| addr.to_string())
+                    .unwrap_or_else(|_| "(unknown)".to_string())
+            );
+            server.listen(listener)?
+        }
+        None => {
+            log::info!("Starting Rust API on port {}", port);
+            let addr = SocketAddr::from(([0, 0, 0, 0], port));
+            server.listen(listener::bind(addr, reuse_address)?)?
+        }
+    };
+    // Signals are handled here on unix so shutdown can be timed for the
+    // report, and drained first when PRE_STOP_DELAY_SECS is set.
+    let server = if cfg!(unix) {
+        server.disable_signals()
+    } else {
+        server
+    };
+    let server = server.run();
+    #[cfg(unix)]
+    drain::handle_signals(draining.clone(), server.handle(), pre_stop_delay);
+    server.await?;
+
+    if let Some(heartbeat) = heartbeat {
+        heartbeat.close().await;
+    }
+    if let Some(server) = grpc_server {
+        server.close().await;
+    }
+    if let Some(pusher) = pusher {
+        pusher.close().await;
+    }
+    // In-flight requests have drained; close dependency connections cleanly.
+    if let Some(listener) = pg_listener {
+        listener.close(close_timeout).await;
+    }
+    log::info!(
+        "Shutdown report: {}",
+        drain::shutdown_report(report_uptime.uptime_seconds(), &report_metrics, &draining)
+    );
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use actix_web::{test, App};
+    use actix_web::test::{self};
+    use actix_web::App;
 
     #[actix_web::test]
     async fn test_root_endpoint() {
-        let app = test::init_service(App::new().service(root)).await;
-        let req = test::TestRequest::get().uri("/").to_request();
+        let table = routes::RouteTable::default();
+        let app = test::init_service(
+            App::new().configure(|cfg| register_routes(cfg, &table, false, false)),
+        )
+        .await;
+        for uri in ["/", "/v1"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let resp = test::call_service(&app, req).await;
+
+            assert!(resp.status().is_success(), "{}", uri);
+
+            let body: ApiInfo = test::read_body_json(resp).await;
+            assert_eq!(body.name, "DevStack Core Rust Reference API");
+            assert_eq!(body.version, "1.0.0");
+            assert_eq!(body.language, "Rust");
+            assert_eq!(body.framework, "Actix-web");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_route_table_matches_registered_routes() {
+        let table = web::Data::new(routes::RouteTable::default());
+        let app = test::init_service(
+            App::new()
+                .app_data(table.clone())
+                .configure(|cfg| register_routes(cfg, &table, true, false)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/debug/routes").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let listed = body["routes"].as_array().unwrap();
+        assert_eq!(listed.len(), table.routes().len());
+        assert!(listed.contains(&serde_json::json!({"method": "POST", "path": "/v1/echo"})));
+        assert!(listed.contains(&serde_json::json!({"method": "GET", "path": "/v1/health/ready"})));
+        assert!(listed.contains(&serde_json::json!({"method": "GET", "path": "/health/ready"})));
+        assert!(!listed.contains(&serde_json::json!({"method": "POST", "path": "/echo"})));
+
+        // Each listed route is really registered; handlers whose app data
+        // is missing here fail with 500, not 404.
+        for route in table.routes() {
+            let uri = route.path.replace("{name}", "vault");
+            let method = actix_web::http::Method::from_bytes(route.method.as_bytes()).unwrap();
+            let req = test::TestRequest::default()
+                .method(method)
+                .uri(&uri)
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_ne!(resp.status(), 404, "{} {}", route.method, route.path);
+        }
+    }
+
+    /// Reads one response off a kept-alive connection, framed by its
+    /// `Content-Length`; returns the status line and the body.
+    async fn read_framed_response<R>(conn: &mut R) -> (String, Vec<u8>)
+    where
+        R: tokio::io::AsyncBufRead + Unpin,
+    {
+        use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+
+        let mut status = String::new();
+        conn.read_line(&mut status).await.unwrap();
+        let mut content_length = None;
+        loop {
+            let mut line = String::new();
+            conn.read_line(&mut line).await.unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            let lower = line.to_ascii_lowercase();
+            assert!(!lower.starts_with("transfer-encoding"), "{}", line);
+            if let Some(value) = lower.strip_prefix("content-length:") {
+                content_length = Some(value.trim().parse::<usize>().unwrap());
+            }
+        }
+        let len = content_length.unwrap_or_else(|| panic!("no content-length: {}", status));
+        let mut body = vec![0; len];
+        conn.read_exact(&mut body).await.unwrap();
+        (status.trim_end().to_string(), body)
+    }
+
+    #[actix_web::test]
+    async fn test_large_responses_keep_connection_framed() {
+        use tokio::io::AsyncWriteExt;
+
+        let config = config::Config {
+            json_field_case: json_case::FieldCase::Camel,
+            ..config::Config::default()
+        };
+        let metrics = web::Data::new(metrics::Metrics::new(&config));
+        // Enough series that the scrape spans several body chunks.
+        for i in 0..400 {
+            metrics.record(
+                "GET",
+                &format!("/items/{}", i),
+                200,
+                Duration::from_millis(1),
+            );
+        }
+        let shared_config = web::Data::new(config);
+        let checks = web::Data::new(
+            checks::HealthChecks::new(
+                shared_config.clone(),
+                web::Data::new(vault::VaultToken::new(None, None)),
+                reqwest::Client::new(),
+                metrics.dependency_in_flight(),
+            )
+            .unwrap(),
+        );
+        let table = web::Data::new(routes::RouteTable::default());
+        let app_metrics = metrics.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(shared_config.clone())
+                .app_data(checks.clone())
+                .app_data(app_metrics.clone())
+                .app_data(table.clone())
+                .wrap(from_fn(json_case::format_json))
+                .wrap(from_fn(cache_control::apply_defaults))
+                .wrap(from_fn(metrics::track_requests))
+                .configure(|cfg| register_routes(cfg, &table, false, false))
+        })
+        .workers(1)
+        .bind("127.0.0.1:0")
+        .unwrap();
+        let addr = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut conn = tokio::io::BufReader::new(stream);
+        let mut sizes = Vec::new();
+        for path in ["/metrics", "/health/all", "/metrics", "/health"] {
+            let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+            conn.get_mut().write_all(request.as_bytes()).await.unwrap();
+            let (status, body) = read_framed_response(&mut conn).await;
+            assert_eq!(status, "HTTP/1.1 200 OK", "{}", path);
+            sizes.push(body.len());
+        }
+        assert!(sizes[0] > 16 * 1024, "scrape is only {} bytes", sizes[0]);
+        handle.stop(false).await;
+    }
+
+    #[actix_web::test]
+    async fn test_info_build_endpoint() {
+        let app = test::init_service(App::new().service(info_build)).await;
+        let req = test::TestRequest::get().uri("/info/build").to_request();
         let resp = test::call_service(&app, req).await;
 
         assert!(resp.status().is_success());
+        assert!(resp.headers().contains_key("etag"));
+        assert_eq!(
+            resp.headers().get("cache-control").unwrap(),
+            "public, max-age=86400"
+        );
 
-        let body: ApiInfo = test::read_body_json(resp).await;
-        assert_eq!(body.name, "DevStack Core Rust Reference API");
+        let body: BuildInfo = test::read_body_json(resp).await;
         assert_eq!(body.version, "1.0.0");
-        assert_eq!(body.language, "Rust");
-        assert_eq!(body.framework, "Actix-web");
+        assert!(!body.git_sha.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_info_build_if_none_match() {
+        let app = test::init_service(App::new().service(info_build)).await;
+        let req = test::TestRequest::get().uri("/info/build").to_request();
+        let resp = test::call_service(&app, req).await;
+        let etag = resp.headers().get("etag").unwrap().clone();
+
+        let req = test::TestRequest::get()
+            .uri("/info/build")
+            .insert_header(("If-None-Match", etag.clone()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_MODIFIED);
+        assert_eq!(resp.headers().get("etag"), Some(&etag));
+
+        let req = test::TestRequest::get()
+            .uri("/info/build")
+            .insert_header(("If-None-Match", "\"stale\""))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_info_uptime_endpoint() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(uptime::Uptime::start(None)))
+                .service(info_uptime),
+        )
+        .await;
+        let req = test::TestRequest::get().uri("/info/uptime").to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert!(resp.status().is_success());
+        assert_eq!(
+            resp.headers().get("cache-control").unwrap(),
+            "public, max-age=1"
+        );
+
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert!(body["started_at"].is_string());
+        assert!(body["uptime_seconds"].is_u64());
+        assert!(body.get("restart_count").is_none());
     }
 
     #[actix_web::test]
     async fn test_health_endpoint() {
-        let app = test::init_service(App::new().service(health)).await;
+        let app = test::init_service(App::new().wrap(NormalizePath::trim()).service(health)).await;
         let req = test::TestRequest::get().uri("/health/").to_request();
         let resp = test::call_service(&app, req).await;
 
         assert!(resp.status().is_success());
 
         let body: HealthResponse = test::read_body_json(resp).await;
+        assert_eq!(body.schema_version, HEALTH_SCHEMA_VERSION);
         assert_eq!(body.status, "healthy");
         assert!(!body.timestamp.is_empty());
     }
 
     #[actix_web::test]
     async fn test_metrics_endpoint() {
-        let app = test::init_service(App::new().service(metrics)).await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(metrics::Metrics::new(&Default::default())))
+                .service(metrics::metrics_endpoint),
+        )
+        .await;
         let req = test::TestRequest::get().uri("/metrics").to_request();
         let resp = test::call_service(&app, req).await;
 
         assert!(resp.status().is_success());
-
-        let body = test::read_body(resp).await;
-        let body_str = std::str::from_utf8(&body).unwrap();
-        assert!(body_str.contains("Rust API metrics placeholder"));
+        let content_type = resp.headers().get("content-type").unwrap();
+        assert!(content_type.to_str().unwrap().starts_with("text/plain"));
     }
 
     #[test]
@@ -154,6 +747,7 @@ mod tests {
     #[test]
     fn test_health_response_serialization() {
         let response = HealthResponse {
+            schema_version: HEALTH_SCHEMA_VERSION,
             status: "healthy".to_string(),
             timestamp: "2025-01-01T00:00:00Z".to_string(),
         };
@@ -162,4 +756,48 @@ mod tests {
         assert!(json.contains("healthy"));
         assert!(json.contains("2025-01-01T00:00:00Z"));
     }
+
+    #[actix_web::test]
+    async fn test_versioned_health_is_exempt() {
+        let host_check = config::Config {
+            allowed_hosts: vec!["api.example".to_string()],
+            ..config::Config::default()
+        };
+        let signed = config::Config {
+            hmac_secret: Some(config::Secret("partner-key".to_string())),
+            ..config::Config::default()
+        };
+        let maintenance = config::HotConfig {
+            maintenance_mode: true,
+            ..config::HotConfig::default()
+        };
+        for (config, hot, rejected) in [
+            (host_check, config::HotConfig::default(), 421),
+            (signed, config::HotConfig::default(), 401),
+            (config::Config::default(), maintenance, 503),
+        ] {
+            let app = test::init_service(
+                App::new()
+                    .app_data(web::Data::new(config))
+                    .app_data(web::Data::new(RwLock::new(hot)))
+                    .app_data(web::Data::new(signature::SeenSignatures::default()))
+                    .wrap(from_fn(signature::verify_signature))
+                    .wrap(from_fn(maintenance::reject_during_maintenance))
+                    .wrap(from_fn(host_check::check_host))
+                    .route("/v1/health/ready", web::get().to(HttpResponse::Ok))
+                    .route("/v1/info/build", web::get().to(HttpResponse::Ok)),
+            )
+            .await;
+            let get = |uri| {
+                test::TestRequest::get()
+                    .uri(uri)
+                    .insert_header(("host", "10.0.0.7:8004"))
+                    .to_request()
+            };
+            let resp = test::call_service(&app, get("/v1/health/ready")).await;
+            assert_eq!(resp.status(), 200, "guard answering {}", rejected);
+            let resp = test::call_service(&app, get("/v1/info/build")).await;
+            assert_eq!(resp.status(), rejected);
+        }
+    }
 }
@@ -0,0 +1,93 @@
+//! Maintenance mode: while `MAINTENANCE_MODE` is on, every route except
+//! health and metrics answers 503 with a `Retry-After` hint, so deploys can
+//! be signalled without tearing the app down.
+
+use std::sync::RwLock;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpResponse};
+
+use crate::config::HotConfig;
+use crate::routes;
+
+/// Probes and scrapes keep working so orchestrators don't mistake
+/// maintenance for an outage.
+fn is_exempt(path: &str) -> bool {
+    routes::is_health(path) || path == "/metrics"
+}
+
+pub async fn reject_during_maintenance(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let retry_after = req
+        .app_data::<web::Data<RwLock<HotConfig>>>()
+        .and_then(|hot| {
+            let hot = hot.read().unwrap_or_else(|e| e.into_inner());
+            hot.maintenance_mode.then_some(hot.maintenance_retry_after)
+        });
+
+    match retry_after {
+        Some(secs) if !is_exempt(req.path()) => {
+            let resp = HttpResponse::ServiceUnavailable()
+                .insert_header((RETRY_AFTER, secs.to_string()))
+                .json(serde_json::json!({
+                    "status": "maintenance",
+                    "retry_after": secs
+                }));
+            Ok(req.into_response(resp))
+        }
+        _ => next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_boxed_body),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::{test, App};
+
+    async fn ok() -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    #[actix_web::test]
+    async fn test_maintenance_mode() {
+        let hot = web::Data::new(RwLock::new(HotConfig {
+            maintenance_mode: true,
+            maintenance_retry_after: 120,
+        }));
+        let app = test::init_service(
+            App::new()
+                .app_data(hot.clone())
+                .wrap(from_fn(reject_during_maintenance))
+                .route("/", web::get().to(ok))
+                .route("/health/", web::get().to(ok))
+                .route("/metrics", web::get().to(ok)),
+        )
+        .await;
+
+        let resp = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+        assert_eq!(resp.status(), 503);
+        assert_eq!(resp.headers().get("retry-after").unwrap(), "120");
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["status"], "maintenance");
+        assert_eq!(body["retry_after"], 120);
+
+        for uri in ["/health/", "/metrics"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            assert!(test::call_service(&app, req).await.status().is_success());
+        }
+
+        // Flipping the flag takes effect on the next request.
+        hot.write().unwrap().maintenance_mode = false;
+        let resp = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+        assert!(resp.status().is_success());
+    }
+}
@@ -0,0 +1,28 @@
+//! Resident memory of this process, for shedding readiness under memory
+//! pressure before the kernel's OOM killer steps in.
+
+use std::fs;
+
+/// Current resident set size in bytes, from `VmRSS` in `/proc/self/status`.
+/// `None` where procfs is unavailable (e.g. running natively on macOS).
+pub fn rss_bytes() -> Option<u64> {
+    parse_vm_rss(&fs::read_to_string("/proc/self/status").ok()?)
+}
+
+fn parse_vm_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_vm_rss() {
+        let status = "Name:\trust-api\nVmPeak:\t  20480 kB\nVmRSS:\t   5120 kB\nThreads:\t4\n";
+        assert_eq!(parse_vm_rss(status), Some(5120 * 1024));
+        assert_eq!(parse_vm_rss("Name:\trust-api\n"), None);
+    }
+}
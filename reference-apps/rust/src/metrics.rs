@@ -0,0 +1,758 @@
+//! Request metrics, exported to Prometheus at `/metrics` and, when
+//! `STATSD_ADDR` is set, mirrored to a StatsD/DogStatsD endpoint.
+//!
+//! Both backends are fed from [`Metrics::record`], the single
+//! instrumentation point, so they always see the same requests.
+//!
+//! `/metrics` speaks OpenMetrics to scrapers that ask for it in `Accept`,
+//! and the classic text format otherwise.
+//!
+//! `http_request_queue_duration_seconds` is the time from accepting a
+//! connection to its first request reaching the handlers: time spent
+//! waiting for a worker as well as reading the request head. Later requests
+//! on a kept-alive connection aren't measured, since their wait includes
+//! however long the client kept it idle.
+//!
+//! Scrape bodies are sent in chunks; a scraper that disconnects before the
+//! last one counts towards `metrics_scrape_aborted_total` and is only
+//! logged at debug level, since Prometheus timeouts and restarts make that
+//! routine.
+//!
+//! With `METRICS_AUTH_TOKEN` set, `/metrics` requires it as a bearer token
+//! or basic auth password (any username), separately from the rest of the
+//! API. Unset, the endpoint is open.
+//!
+//! `http_requests_in_flight` counts requests inside the handlers; its peak
+//! since startup goes into the shutdown report.
+//!
+//! Label values are sanitized before use: clients choose the HTTP method,
+//! so nothing stops them from sending a megabyte of one.
+
+use std::any::Any;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::net::UdpSocket;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use actix_web::body::{BodySize, BoxBody, MessageBody};
+use actix_web::dev::{Extensions, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{ACCEPT, AUTHORIZATION, VARY};
+use actix_web::middleware::Next;
+use actix_web::{get, web, Error, HttpRequest, HttpResponse};
+use base64::Engine;
+use cadence::prelude::*;
+use cadence::{BufferedUdpMetricSink, MetricResult, QueuingMetricSink, StatsdClient};
+use prometheus::core::Collector;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+use subtle::ConstantTimeEq;
+
+use crate::config::Config;
+use crate::error::ApiError;
+
+/// Route label for requests that matched no registered resource, so
+/// arbitrary paths can't blow up label cardinality.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+const OPENMETRICS_TYPE: &str = "application/openmetrics-text";
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    duration: HistogramVec,
+    queue_duration: Histogram,
+    scrapes_aborted: IntCounter,
+    labels_sanitized: IntCounter,
+    /// Whether a sanitized label has been logged yet; later ones are only
+    /// counted, since clients choose the values.
+    sanitized_logged: AtomicBool,
+    in_flight: IntGauge,
+    peak_in_flight: AtomicI64,
+    dependency_in_flight: IntGaugeVec,
+    readiness_state: IntGaugeVec,
+    statsd: Option<Statsd>,
+    label_max_len: usize,
+}
+
+struct Statsd {
+    client: StatsdClient,
+    sample_rate: f64,
+}
+
+impl Metrics {
+    pub fn new(config: &Config) -> Metrics {
+        let registry = Registry::new();
+        let requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled"),
+            &["method", "route", "status"],
+        )
+        .expect("valid metric");
+        let duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "HTTP request latency"),
+            &["method", "route"],
+        )
+        .expect("valid metric");
+        registry
+            .register(Box::new(requests.clone()))
+            .expect("metric registered once");
+        let queue_duration = Histogram::with_opts(HistogramOpts::new(
+            "http_request_queue_duration_seconds",
+            "Time from connection accept to the first request reaching a handler",
+        ))
+        .expect("valid metric");
+        registry
+            .register(Box::new(queue_duration.clone()))
+            .expect("metric registered once");
+        let scrapes_aborted = IntCounter::new(
+            "metrics_scrape_aborted_total",
+            "Scrapes whose client disconnected before the body was sent",
+        )
+        .expect("valid metric");
+        registry
+            .register(Box::new(scrapes_aborted.clone()))
+            .expect("metric registered once");
+        let labels_sanitized = IntCounter::new(
+            "metric_labels_sanitized_total",
+            "Label values truncated or rewritten before being recorded",
+        )
+        .expect("valid metric");
+        registry
+            .register(Box::new(labels_sanitized.clone()))
+            .expect("metric registered once");
+        let in_flight = IntGauge::new(
+            "http_requests_in_flight",
+            "HTTP requests currently being handled",
+        )
+        .expect("valid metric");
+        registry
+            .register(Box::new(in_flight.clone()))
+            .expect("metric registered once");
+        let dependency_in_flight = IntGaugeVec::new(
+            Opts::new(
+                "dependency_checks_in_flight",
+                "Dependency health probes currently running",
+            ),
+            &["dependency"],
+        )
+        .expect("valid metric");
+        registry
+            .register(Box::new(duration.clone()))
+            .expect("metric registered once");
+        registry
+            .register(Box::new(dependency_in_flight.clone()))
+            .expect("metric registered once");
+        let readiness_state = IntGaugeVec::new(
+            Opts::new(
+                "readiness_state",
+                "1 for the current readiness state (warming, ready or not_ready)",
+            ),
+            &["state"],
+        )
+        .expect("valid metric");
+        registry
+            .register(Box::new(readiness_state.clone()))
+            .expect("metric registered once");
+
+        let statsd = config.statsd_addr.as_deref().and_then(|addr| {
+            match statsd_client(addr, &config.statsd_prefix) {
+                Ok(client) => {
+                    log::info!(
+                        "Mirroring metrics to StatsD at {} (sample rate {})",
+                        addr,
+                        config.statsd_sample_rate
+                    );
+                    Some(Statsd {
+                        client,
+                        sample_rate: config.statsd_sample_rate.clamp(0.0, 1.0),
+                    })
+                }
+                Err(e) => {
+                    log::error!("StatsD disabled: failed to set up sink for {}: {}", addr, e);
+                    None
+                }
+            }
+        });
+
+        Metrics {
+            registry,
+            requests,
+            duration,
+            queue_duration,
+            scrapes_aborted,
+            labels_sanitized,
+            sanitized_logged: AtomicBool::new(false),
+            in_flight,
+            peak_in_flight: AtomicI64::new(0),
+            dependency_in_flight,
+            readiness_state,
+            statsd,
+            label_max_len: config.metric_label_max_len,
+        }
+    }
+
+    /// In-flight probes per dependency, kept current by the check bulkheads.
+    pub fn dependency_in_flight(&self) -> &IntGaugeVec {
+        &self.dependency_in_flight
+    }
+
+    /// Readiness state, kept current by the readiness evaluation.
+    pub fn readiness_state(&self) -> &IntGaugeVec {
+        &self.readiness_state
+    }
+
+    /// Requests recorded since startup, across every label.
+    pub fn requests_served(&self) -> u64 {
+        self.requests
+            .collect()
+            .iter()
+            .flat_map(|family| family.get_metric())
+            .map(|metric| metric.get_counter().get_value())
+            .sum::<f64>() as u64
+    }
+
+    /// The most requests in flight at once since startup.
+    pub fn peak_in_flight(&self) -> i64 {
+        self.peak_in_flight.load(Ordering::Relaxed)
+    }
+
+    /// `value` made safe to use as a label, counting (and logging the first
+    /// of) the values that needed changing.
+    fn label<'a>(&self, value: &'a str) -> Cow<'a, str> {
+        let label = sanitize_label(value, self.label_max_len);
+        if let Cow::Owned(sanitized) = &label {
+            self.labels_sanitized.inc();
+            if !self.sanitized_logged.swap(true, Ordering::Relaxed) {
+                log::warn!(
+                    "Sanitized metric label value ({} bytes) to '{}'; further ones are \
+                     counted in metric_labels_sanitized_total",
+                    value.len(),
+                    sanitized
+                );
+            }
+        }
+        label
+    }
+
+    /// Records one finished request in every backend.
+    pub fn record(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let method = &*self.label(method);
+        let route = &*self.label(route);
+        let status = status.to_string();
+        self.requests
+            .with_label_values(&[method, route, status.as_str()])
+            .inc();
+        self.duration
+            .with_label_values(&[method, route])
+            .observe(elapsed.as_secs_f64());
+
+        if let Some(statsd) = &self.statsd {
+            // StatsD sampling is client-side: send with probability `sample_rate`
+            // and tag the metric with it so the server scales the counts back up.
+            let sampled = statsd.sample_rate < 1.0;
+            if sampled && fastrand::f64() >= statsd.sample_rate {
+                return;
+            }
+            let mut count = statsd
+                .client
+                .count_with_tags("http.requests", 1)
+                .with_tag("method", method)
+                .with_tag("route", route)
+                .with_tag("status", &status);
+            let mut time = statsd
+                .client
+                .time_with_tags("http.request_duration", elapsed)
+                .with_tag("method", method)
+                .with_tag("route", route);
+            if sampled {
+                count = count.with_sampling_rate(statsd.sample_rate);
+                time = time.with_sampling_rate(statsd.sample_rate);
+            }
+            let _ = count.try_send();
+            let _ = time.try_send();
+        }
+    }
+
+    /// The registry in the classic text format.
+    pub fn render(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buf) {
+            log::error!("Failed to encode metrics: {}", e);
+        }
+        buf
+    }
+
+    fn render_openmetrics(&self) -> Vec<u8> {
+        let text = String::from_utf8(self.render()).unwrap_or_default();
+        to_openmetrics(&text).into_bytes()
+    }
+}
+
+/// Scrape chunk size: large enough that a typical scrape is a few writes.
+const SCRAPE_CHUNK_BYTES: usize = 16 * 1024;
+
+/// A rendered scrape, sent in chunks. Dropped before the last chunk went
+/// out means the scraper went away mid-response.
+struct ScrapeBody {
+    body: web::Bytes,
+    sent: usize,
+    aborted: IntCounter,
+}
+
+impl MessageBody for ScrapeBody {
+    type Error = std::convert::Infallible;
+
+    fn size(&self) -> BodySize {
+        BodySize::Sized(self.body.len() as u64)
+    }
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+    ) -> Poll<Option<Result<web::Bytes, Self::Error>>> {
+        let this = self.get_mut();
+        if this.sent == this.body.len() {
+            return Poll::Ready(None);
+        }
+        let end = (this.sent + SCRAPE_CHUNK_BYTES).min(this.body.len());
+        let chunk = this.body.slice(this.sent..end);
+        this.sent = end;
+        Poll::Ready(Some(Ok(chunk)))
+    }
+}
+
+impl Drop for ScrapeBody {
+    fn drop(&mut self) {
+        if self.sent < self.body.len() {
+            log::debug!(
+                "Metrics scrape aborted after {} of {} bytes",
+                self.sent,
+                self.body.len()
+            );
+            self.aborted.inc();
+        }
+    }
+}
+
+/// Truncates `value` to `max_len` bytes (on a character boundary; 0 means
+/// no limit) and replaces control characters and the StatsD separators `,`,
+/// `|` and `#` with `_`. Values needing neither are returned as-is.
+fn sanitize_label(value: &str, max_len: usize) -> Cow<'_, str> {
+    let disallowed = |c: char| c.is_control() || matches!(c, ',' | '|' | '#');
+    let max_len = if max_len == 0 { usize::MAX } else { max_len };
+    if value.len() <= max_len && !value.contains(disallowed) {
+        return Cow::Borrowed(value);
+    }
+
+    let mut end = value.len().min(max_len);
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    let sanitized: String = value[..end]
+        .chars()
+        .map(|c| if disallowed(c) { '_' } else { c })
+        .collect();
+    Cow::Owned(sanitized)
+}
+
+/// Rewrites the classic text format as OpenMetrics. The prometheus crate
+/// has no OpenMetrics encoder, but for counters, gauges and histograms the
+/// formats differ only in that a counter family drops the `_total` suffix
+/// its samples keep, and the exposition ends with `# EOF`.
+fn to_openmetrics(text: &str) -> String {
+    let counters: Vec<&str> = text
+        .lines()
+        .filter_map(|line| line.strip_prefix("# TYPE "))
+        .filter_map(|rest| rest.strip_suffix(" counter"))
+        .collect();
+    let mut out = String::with_capacity(text.len() + 6);
+    for line in text.lines() {
+        let rewritten = ["# HELP ", "# TYPE "].iter().find_map(|prefix| {
+            let rest = line.strip_prefix(prefix)?;
+            let (name, tail) = rest.split_once(' ')?;
+            let family = name.strip_suffix("_total")?;
+            counters
+                .contains(&name)
+                .then(|| format!("{}{} {}", prefix, family, tail))
+        });
+        out.push_str(rewritten.as_deref().unwrap_or(line));
+        out.push('\n');
+    }
+    out.push_str("# EOF\n");
+    out
+}
+
+fn wants_openmetrics(req: &HttpRequest) -> bool {
+    req.headers()
+        .get_all(ACCEPT)
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|media| {
+            media
+                .split(';')
+                .next()
+                .is_some_and(|t| t.trim().eq_ignore_ascii_case(OPENMETRICS_TYPE))
+        })
+}
+
+/// Buffered and queued, so sending never blocks a request on the socket.
+fn statsd_client(addr: &str, prefix: &str) -> MetricResult<StatsdClient> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.set_nonblocking(true)?;
+    let sink = BufferedUdpMetricSink::from(addr, socket)?;
+    let client = StatsdClient::builder(prefix, QueuingMetricSink::from(sink))
+        .with_error_handler(|e| log::debug!("StatsD send failed: {}", e))
+        .build();
+    Ok(client)
+}
+
+/// When a connection was accepted; taken by its first request.
+struct Accepted(Cell<Option<Instant>>);
+
+/// `HttpServer::on_connect` hook stamping each connection for queue time.
+pub fn stamp_connection(_: &dyn Any, extensions: &mut Extensions) {
+    extensions.insert(Accepted(Cell::new(Some(Instant::now()))));
+}
+
+/// Times every request and records it against its route pattern.
+pub async fn track_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let Some(metrics) = req.app_data::<web::Data<Metrics>>().cloned() else {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_boxed_body);
+    };
+
+    if let Some(accepted) = req.conn_data::<Accepted>().and_then(|a| a.0.take()) {
+        metrics
+            .queue_duration
+            .observe(accepted.elapsed().as_secs_f64());
+    }
+    let method = req.method().to_string();
+    let route = req
+        .match_pattern()
+        .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+    let start = Instant::now();
+    let in_flight = InFlightRequest::start(&metrics);
+    let result = next.call(req).await;
+    drop(in_flight);
+
+    let status = match &result {
+        Ok(resp) => resp.status(),
+        Err(e) => e.as_response_error().status_code(),
+    };
+    metrics.record(&method, &route, status.as_u16(), start.elapsed());
+    result.map(ServiceResponse::map_into_boxed_body)
+}
+
+/// Counts a request in flight until dropped, including when the client goes
+/// away mid-request.
+struct InFlightRequest<'a>(&'a Metrics);
+
+impl<'a> InFlightRequest<'a> {
+    fn start(metrics: &'a Metrics) -> InFlightRequest<'a> {
+        metrics.in_flight.inc();
+        metrics
+            .peak_in_flight
+            .fetch_max(metrics.in_flight.get(), Ordering::Relaxed);
+        InFlightRequest(metrics)
+    }
+}
+
+impl Drop for InFlightRequest<'_> {
+    fn drop(&mut self) {
+        self.0.in_flight.dec();
+    }
+}
+
+/// Whether `Authorization` carries `token`, as `Bearer <token>` or as the
+/// password of `Basic` credentials.
+fn authorized(req: &HttpRequest, token: &str) -> bool {
+    let Some(header) = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return false;
+    };
+    let (scheme, credentials) = header.trim().split_once(' ').unwrap_or((header, ""));
+    let presented = if scheme.eq_ignore_ascii_case("bearer") {
+        credentials.trim().as_bytes().to_vec()
+    } else if scheme.eq_ignore_ascii_case("basic") {
+        let Ok(decoded) = base64::engine::general_purpose::STANDARD.decode(credentials.trim())
+        else {
+            return false;
+        };
+        match decoded.iter().position(|&b| b == b':') {
+            Some(colon) => decoded[colon + 1..].to_vec(),
+            None => return false,
+        }
+    } else {
+        return false;
+    };
+    presented.ct_eq(token.as_bytes()).into()
+}
+
+#[get("/metrics")]
+pub async fn metrics_endpoint(
+    req: HttpRequest,
+    metrics: web::Data<Metrics>,
+    config: Option<web::Data<Config>>,
+) -> Result<HttpResponse, ApiError> {
+    let token = config.as_ref().and_then(|c| c.metrics_auth_token.as_ref());
+    if token.is_some_and(|token| !authorized(&req, token.expose())) {
+        return Err(ApiError::Unauthorized { realm: "metrics" });
+    }
+    let mut resp = HttpResponse::Ok();
+    resp.insert_header((VARY, "Accept"));
+    let body = if wants_openmetrics(&req) {
+        resp.content_type(OPENMETRICS_CONTENT_TYPE);
+        metrics.render_openmetrics()
+    } else {
+        resp.content_type(TextEncoder::new().format_type());
+        metrics.render()
+    };
+    // HEAD responses drop the body unsent, which isn't an abort.
+    if req.method() == actix_web::http::Method::HEAD {
+        return Ok(resp.body(body));
+    }
+    Ok(resp.body(ScrapeBody {
+        body: body.into(),
+        sent: 0,
+        aborted: metrics.scrapes_aborted.clone(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{self};
+    use actix_web::App;
+
+    async fn ok() -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    #[actix_web::test]
+    async fn test_requests_are_recorded_by_route() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(Metrics::new(&Config::default())))
+                .wrap(from_fn(track_requests))
+                .route("/items/{id}", web::get().to(ok))
+                .service(metrics_endpoint),
+        )
+        .await;
+
+        for uri in ["/items/1", "/items/2", "/nope"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            test::call_service(&app, req).await;
+        }
+
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body
+            .contains(r#"http_requests_total{method="GET",route="/items/{id}",status="200"} 2"#));
+        assert!(
+            body.contains(r#"http_requests_total{method="GET",route="unmatched",status="404"} 1"#)
+        );
+        assert!(body.contains("http_request_duration_seconds_bucket"));
+    }
+
+    #[actix_web::test]
+    async fn test_queue_time_is_recorded_once_per_connection() {
+        let metrics = web::Data::new(Metrics::new(&Config::default()));
+        let app_metrics = metrics.clone();
+        let server = actix_web::HttpServer::new(move || {
+            App::new()
+                .app_data(app_metrics.clone())
+                .wrap(from_fn(track_requests))
+                .route("/", web::get().to(ok))
+        })
+        .workers(1)
+        .on_connect(stamp_connection)
+        .bind("127.0.0.1:0")
+        .unwrap();
+        let addr = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        // The second request reuses the pooled connection.
+        let http = reqwest::Client::new();
+        for _ in 0..2 {
+            let resp = http.get(format!("http://{}/", addr)).send().await.unwrap();
+            assert_eq!(resp.status(), 200);
+        }
+        let body = String::from_utf8(metrics.render()).unwrap();
+        assert!(
+            body.contains("http_request_queue_duration_seconds_count 1"),
+            "{}",
+            body
+        );
+        handle.stop(false).await;
+    }
+
+    #[actix_web::test]
+    async fn test_scrape_dropped_mid_body_counts_as_aborted() {
+        let counter = IntCounter::new("aborted", "test").unwrap();
+        let scrape = |len| ScrapeBody {
+            body: vec![b'x'; len].into(),
+            sent: 0,
+            aborted: counter.clone(),
+        };
+
+        let body = actix_web::body::to_bytes(scrape(SCRAPE_CHUNK_BYTES * 2 + 1))
+            .await
+            .unwrap();
+        assert_eq!(body.len(), SCRAPE_CHUNK_BYTES * 2 + 1);
+        assert_eq!(counter.get(), 0);
+
+        let mut partial = Box::pin(scrape(SCRAPE_CHUNK_BYTES * 2));
+        let first = futures_util::future::poll_fn(|cx| partial.as_mut().poll_next(cx)).await;
+        assert_eq!(first.unwrap().unwrap().len(), SCRAPE_CHUNK_BYTES);
+        drop(partial);
+        assert_eq!(counter.get(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_metrics_auth_token() {
+        let config = Config {
+            metrics_auth_token: Some(crate::config::Secret("scrape-me".to_string())),
+            ..Config::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(Metrics::new(&config)))
+                .app_data(web::Data::new(config))
+                .service(metrics_endpoint),
+        )
+        .await;
+
+        let basic = format!(
+            "Basic {}",
+            base64::engine::general_purpose::STANDARD.encode("prometheus:scrape-me")
+        );
+        for (authorization, status) in [
+            (None, 401),
+            (Some("Bearer wrong"), 401),
+            (Some("Bearer scrape-me-not"), 401),
+            (Some("Basic !!!"), 401),
+            (Some("Bearer scrape-me"), 200),
+            (Some(basic.as_str()), 200),
+        ] {
+            let mut req = test::TestRequest::get().uri("/metrics");
+            if let Some(authorization) = authorization {
+                req = req.insert_header((AUTHORIZATION, authorization));
+            }
+            let resp = test::call_service(&app, req.to_request()).await;
+            assert_eq!(resp.status(), status, "{:?}", authorization);
+            if status == 401 {
+                assert!(resp.headers().contains_key("www-authenticate"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_sanitize_label() {
+        assert!(matches!(
+            sanitize_label("/items/{id}", 16),
+            Cow::Borrowed("/items/{id}")
+        ));
+        assert_eq!(sanitize_label("GET|x,y#z\n", 16), "GET_x_y_z_");
+        assert_eq!(sanitize_label(&"A".repeat(1000), 16), "A".repeat(16));
+        assert_eq!(sanitize_label(&"A".repeat(1000), 0), "A".repeat(1000));
+        // Truncation doesn't split a multi-byte character.
+        assert_eq!(sanitize_label("ééé", 5), "éé");
+
+        let metrics = Metrics::new(&Config::default());
+        for _ in 0..3 {
+            metrics.record("GET|x", "/health", 200, Duration::from_millis(1));
+        }
+        metrics.record("GET", "/health", 200, Duration::from_millis(1));
+        assert_eq!(metrics.labels_sanitized.get(), 3);
+    }
+
+    #[actix_web::test]
+    async fn test_openmetrics_negotiation() {
+        let metrics = Metrics::new(&Config::default());
+        metrics.record("GET", "/health", 200, Duration::from_millis(3));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(metrics))
+                .service(metrics_endpoint),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/metrics")
+            .insert_header((
+                ACCEPT,
+                "application/openmetrics-text;version=1.0.0,text/plain;q=0.5",
+            ))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(
+            resp.headers().get("content-type").unwrap(),
+            OPENMETRICS_CONTENT_TYPE
+        );
+        let body = test::read_body(resp).await;
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains("# TYPE http_requests counter\n"));
+        assert!(
+            body.contains(r#"http_requests_total{method="GET",route="/health",status="200"} 1"#)
+        );
+        assert!(body.contains("# TYPE http_request_duration_seconds histogram\n"));
+        assert!(body.ends_with("# EOF\n"));
+
+        let req = test::TestRequest::get()
+            .uri("/metrics")
+            .insert_header((ACCEPT, "text/plain"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(
+            resp.headers().get("content-type").unwrap(),
+            TextEncoder::new().format_type()
+        );
+        let body = test::read_body(resp).await;
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains("# TYPE http_requests_total counter\n"));
+        assert!(!body.contains("# EOF"));
+    }
+
+    #[test]
+    fn test_statsd_mirrors_requests() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let config = Config {
+            statsd_addr: Some(server.local_addr().unwrap().to_string()),
+            statsd_prefix: "rust_api".to_string(),
+            statsd_sample_rate: 1.0,
+            ..Config::default()
+        };
+
+        let metrics = Metrics::new(&config);
+        metrics.record("GET", "/health", 200, Duration::from_millis(3));
+        // Dropping the client flushes the buffered sink.
+        drop(metrics);
+
+        let mut buf = [0u8; 1024];
+        let len = server.recv(&mut buf).unwrap();
+        let packet = std::str::from_utf8(&buf[..len]).unwrap();
+        assert!(packet.contains("rust_api.http.requests:1|c|#method:GET,route:/health,status:200"));
+        assert!(packet.contains("rust_api.http.request_duration:3|ms"));
+    }
+}
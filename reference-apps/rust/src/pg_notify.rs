@@ -0,0 +1,144 @@
+//! Fleet-wide config reload via Postgres `LISTEN/NOTIFY`.
+//!
+//! With `ENABLE_PG_CONFIG_NOTIFY=true` each replica listens on
+//! `CONFIG_NOTIFY_CHANNEL` and reloads [`HotConfig`] (the same subset as
+//! SIGHUP) on every notification, so a single
+//! `NOTIFY config_reload` propagates a change to all of them.
+//!
+//! On shutdown the connection is closed with a proper `Terminate` rather
+//! than dropped, so the server doesn't keep the session around.
+
+use std::sync::RwLock;
+use std::time::Duration;
+
+use actix_web::web;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio_postgres::{AsyncMessage, NoTls};
+
+use crate::config::{Config, HotConfig, PostgresConfig};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// A running listener, closed with [`Listener::close`].
+pub struct Listener {
+    shutdown: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl Listener {
+    /// Closes the connection, waiting at most `timeout` for it to finish.
+    pub async fn close(self, timeout: Duration) {
+        let _ = self.shutdown.send(true);
+        match tokio::time::timeout(timeout, self.task).await {
+            Ok(_) => log::info!("Closed Postgres config listener connection"),
+            Err(_) => log::warn!(
+                "Postgres config listener did not close within {}s",
+                timeout.as_secs()
+            ),
+        }
+    }
+}
+
+/// Starts the listener; it reconnects until closed.
+pub fn spawn_listener(config: &Config, hot_config: web::Data<RwLock<HotConfig>>) -> Listener {
+    let postgres = config.postgres.clone();
+    let channel = config.config_notify_channel.clone();
+    let (shutdown, mut closing) = watch::channel(false);
+
+    let task = actix_web::rt::spawn(async move {
+        while !*closing.borrow() {
+            if let Err(e) = listen(&postgres, &channel, &hot_config, closing.clone()).await {
+                log::warn!(
+                    "Postgres config listener on '{}' failed: {}; reconnecting in {}s",
+                    channel,
+                    e,
+                    RECONNECT_DELAY.as_secs()
+                );
+            }
+            tokio::select! {
+                // An error means the `Listener` was dropped: stop as well.
+                changed = closing.changed() => if changed.is_err() { break },
+                _ = tokio::time::sleep(RECONNECT_DELAY) => {}
+            }
+        }
+    });
+    Listener { shutdown, task }
+}
+
+async fn listen(
+    postgres: &PostgresConfig,
+    channel: &str,
+    hot_config: &RwLock<HotConfig>,
+    mut closing: watch::Receiver<bool>,
+) -> Result<(), tokio_postgres::Error> {
+    let (client, mut connection) = tokio_postgres::Config::new()
+        .host(&postgres.host)
+        .port(postgres.port)
+        .user(&postgres.user)
+        .password(&postgres.password)
+        .dbname(&postgres.dbname)
+        .application_name("devstack-core-rust-api")
+        .connect(NoTls)
+        .await?;
+
+    // Notifications arrive on the connection, which must be polled for the
+    // client to make progress at all, so drive it from its own task.
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let driver = actix_web::rt::spawn(async move {
+        while let Some(message) = std::future::poll_fn(|cx| connection.poll_message(cx)).await {
+            match message {
+                Ok(AsyncMessage::Notification(n)) => {
+                    if tx.send(n.payload().to_string()).is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    });
+
+    client
+        .batch_execute(&format!("LISTEN {}", quote_ident(channel)))
+        .await?;
+    log::info!(
+        "Listening for config reloads on Postgres channel '{}'",
+        channel
+    );
+
+    loop {
+        tokio::select! {
+            _ = closing.changed() => break,
+            payload = rx.recv() => {
+                let Some(payload) = payload else { break };
+                log::debug!("Config notification payload: {:?}", payload);
+                HotConfig::reload_into(hot_config, &format!("NOTIFY {}", channel));
+            }
+        }
+    }
+
+    // Dropping the client makes the connection send `Terminate` and finish.
+    drop(client);
+    match driver.await {
+        Ok(result) => result,
+        Err(_) => Ok(()),
+    }
+}
+
+/// Quotes a Postgres identifier so any channel name is safe to interpolate.
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_ident() {
+        assert_eq!(quote_ident("config_reload"), "\"config_reload\"");
+        assert_eq!(quote_ident("a\"; DROP"), "\"a\"\"; DROP\"");
+    }
+}
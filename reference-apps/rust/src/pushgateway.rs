@@ -0,0 +1,174 @@
+//! Push mode for environments that can't scrape us: with `PUSHGATEWAY_URL`
+//! set, the registry is pushed every `PUSHGATEWAY_INTERVAL_SECS` to
+//! `<url>/metrics/job/<PUSHGATEWAY_JOB>/<label>/<value>...`, once more on
+//! shutdown so a short-lived process's final numbers land.
+//!
+//! This speaks the Pushgateway protocol over the app's own async client
+//! rather than the prometheus crate's `push` feature, which drags in a
+//! blocking, native-TLS reqwest.
+
+use std::time::Duration;
+
+use actix_web::web;
+use base64::Engine;
+use prometheus::{Encoder, TextEncoder};
+use reqwest::Url;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use crate::config::Config;
+use crate::metrics::Metrics;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `<base>/metrics/job/<job>/<label>/<value>...`. Values that would break
+/// the path (empty, or containing `/`) use the `<label>@base64/` form.
+fn push_url(base: &str, job: &str, grouping: &[(String, String)]) -> Result<Url, String> {
+    let mut url = Url::parse(base).map_err(|e| format!("invalid PUSHGATEWAY_URL: {}", e))?;
+    {
+        let mut segments = url
+            .path_segments_mut()
+            .map_err(|_| "invalid PUSHGATEWAY_URL: not a base URL".to_string())?;
+        segments.pop_if_empty();
+        segments.extend(["metrics", "job", job]);
+        for (label, value) in grouping {
+            if value.is_empty() || value.contains('/') {
+                let encoded = base64::engine::general_purpose::URL_SAFE.encode(value);
+                segments.push(&format!("{}@base64", label));
+                segments.push(if encoded.is_empty() { "=" } else { &encoded });
+            } else {
+                segments.extend([label, value]);
+            }
+        }
+    }
+    Ok(url)
+}
+
+async fn push(http: &reqwest::Client, url: &Url, metrics: &Metrics) -> Result<(), String> {
+    let resp = http
+        .put(url.clone())
+        .header(
+            reqwest::header::CONTENT_TYPE,
+            TextEncoder::new().format_type(),
+        )
+        .body(metrics.render())
+        .timeout(TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| format!("request failed: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    Ok(())
+}
+
+/// A running push loop; [`Pusher::close`] pushes a last time and stops it.
+pub struct Pusher {
+    shutdown: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl Pusher {
+    pub async fn close(self) {
+        let _ = self.shutdown.send(());
+        let _ = self.task.await;
+    }
+}
+
+/// `None` unless `PUSHGATEWAY_URL` is set (and valid).
+pub fn start(
+    config: &Config,
+    http: reqwest::Client,
+    metrics: web::Data<Metrics>,
+) -> Option<Pusher> {
+    let base = config.pushgateway_url.as_deref()?;
+    let url = match push_url(base, &config.pushgateway_job, &config.pushgateway_grouping) {
+        Ok(url) => url,
+        Err(e) => {
+            log::error!("Pushgateway disabled: {}", e);
+            return None;
+        }
+    };
+    let interval = Duration::from_secs(config.pushgateway_interval_secs.max(1));
+    log::info!("Pushing metrics to {} every {}s", url, interval.as_secs());
+    let (shutdown, mut closing) = oneshot::channel();
+
+    let task = actix_web::rt::spawn(async move {
+        let mut failing = false;
+        loop {
+            let stop = tokio::select! {
+                _ = tokio::time::sleep(interval) => false,
+                _ = &mut closing => true,
+            };
+            match push(&http, &url, &metrics).await {
+                // Log the first failure of a run and the recovery, not every push.
+                Err(e) if !failing => {
+                    log::warn!("Pushgateway push to {} failed: {}", url, e);
+                    failing = true;
+                }
+                Err(_) => {}
+                Ok(()) if failing => {
+                    log::info!("Pushgateway push to {} recovered", url);
+                    failing = false;
+                }
+                Ok(()) => {}
+            }
+            if stop {
+                break;
+            }
+        }
+    });
+    Some(Pusher { shutdown, task })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_push_url() {
+        let grouping = vec![
+            ("instance".to_string(), "pod-7".to_string()),
+            ("path".to_string(), "/var/tmp".to_string()),
+            ("empty".to_string(), String::new()),
+        ];
+        let url = push_url("http://pushgateway:9091/", "rust-api", &grouping).unwrap();
+        assert_eq!(
+            url.as_str(),
+            "http://pushgateway:9091/metrics/job/rust-api/instance/pod-7\
+             /path@base64/L3Zhci90bXA=/empty@base64/="
+        );
+        assert!(push_url("not a url", "job", &[]).is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_pushes_on_close() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/metrics/job/rust-api/instance/pod-7"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = Config {
+            pushgateway_url: Some(server.uri()),
+            pushgateway_job: "rust-api".to_string(),
+            pushgateway_grouping: vec![("instance".to_string(), "pod-7".to_string())],
+            pushgateway_interval_secs: 3600,
+            ..Config::default()
+        };
+        let metrics = web::Data::new(Metrics::new(&config));
+        metrics.record("GET", "/health", 200, Duration::from_millis(3));
+        let pusher = start(&config, reqwest::Client::new(), metrics).unwrap();
+        pusher.close().await;
+
+        let pushed = &server.received_requests().await.unwrap()[0];
+        let body = String::from_utf8_lossy(&pushed.body);
+        assert!(
+            body.contains("http_requests_total{method=\"GET\",route=\"/health\",status=\"200\"} 1")
+        );
+    }
+}
@@ -0,0 +1,261 @@
+//! Request recorder: with `RECORD_SAMPLE_RATE` above 0, a sample of requests
+//! is appended to `RECORD_FILE` as JSON lines, with sensitive headers
+//! redacted and the body kept up to `RECORD_BODY_MAX_BYTES`, as repro input.
+//! Once the file reaches `RECORD_MAX_BYTES` it is rotated to `<file>.1`,
+//! replacing any earlier rotation.
+
+use std::cell::RefCell;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Mutex;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::header::HeaderMap;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpMessage};
+use futures_util::StreamExt;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::config::Config;
+use crate::timestamp;
+
+const REDACTED_HEADERS: &[&str] = &[
+    "authorization",
+    "cookie",
+    "proxy-authorization",
+    "set-cookie",
+    "x-vault-token",
+];
+
+#[derive(Debug, Serialize)]
+struct Recording {
+    timestamp: String,
+    method: String,
+    path: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    query: String,
+    headers: Map<String, Value>,
+    body: String,
+    body_bytes: usize,
+    body_truncated: bool,
+    status: u16,
+}
+
+/// The body as the handler read it, up to the cap.
+#[derive(Default)]
+struct Capture {
+    data: Vec<u8>,
+    total: usize,
+}
+
+pub struct Recorder {
+    path: PathBuf,
+    sample_rate: f64,
+    max_bytes: u64,
+    body_max_bytes: usize,
+    file: Mutex<Option<File>>,
+}
+
+impl Recorder {
+    /// `None` unless recording is switched on.
+    pub fn from_config(config: &Config) -> Option<Recorder> {
+        if config.record_sample_rate <= 0.0 {
+            return None;
+        }
+        log::info!(
+            "Recording {} of requests to {}",
+            config.record_sample_rate.min(1.0),
+            config.record_file.display()
+        );
+        Some(Recorder {
+            path: config.record_file.clone(),
+            sample_rate: config.record_sample_rate,
+            max_bytes: config.record_max_bytes,
+            body_max_bytes: config.record_body_max_bytes,
+            file: Mutex::new(None),
+        })
+    }
+
+    fn write(&self, recording: &Recording) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(recording)?;
+        line.push(b'\n');
+
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if file.is_none() {
+            *file = Some(self.open()?);
+        }
+        let size = file
+            .as_ref()
+            .map_or(Ok(0), |f| f.metadata().map(|m| m.len()))?;
+        if size > 0 && size + line.len() as u64 > self.max_bytes {
+            *file = None;
+            fs::rename(
+                &self.path,
+                self.path.with_extension(rotated_extension(&self.path)),
+            )?;
+            *file = Some(self.open()?);
+        }
+        match file.as_mut() {
+            Some(f) => f.write_all(&line),
+            None => Ok(()),
+        }
+    }
+
+    fn open(&self) -> std::io::Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+    }
+}
+
+/// `requests.jsonl` rotates to `requests.jsonl.1`.
+fn rotated_extension(path: &std::path::Path) -> String {
+    match path.extension() {
+        Some(ext) => format!("{}.1", ext.to_string_lossy()),
+        None => "1".to_string(),
+    }
+}
+
+fn redacted_headers(headers: &HeaderMap) -> Map<String, Value> {
+    let mut out = Map::new();
+    for (name, value) in headers {
+        let value = if REDACTED_HEADERS.contains(&name.as_str()) {
+            "<redacted>".to_string()
+        } else {
+            String::from_utf8_lossy(value.as_bytes()).into_owned()
+        };
+        out.insert(name.to_string(), value.into());
+    }
+    out
+}
+
+pub async fn record_requests(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let recorder = req
+        .app_data::<web::Data<Recorder>>()
+        .filter(|r| fastrand::f64() < r.sample_rate)
+        .cloned();
+    let Some(recorder) = recorder else {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_boxed_body);
+    };
+
+    let mut recording = Recording {
+        timestamp: timestamp::now(),
+        method: req.method().to_string(),
+        path: req.path().to_string(),
+        query: req.query_string().to_string(),
+        headers: redacted_headers(req.headers()),
+        body: String::new(),
+        body_bytes: 0,
+        body_truncated: false,
+        status: 0,
+    };
+
+    // Copy the body as the handler streams it, so nothing is buffered twice.
+    let capture = Rc::new(RefCell::new(Capture::default()));
+    let sink = capture.clone();
+    let cap = recorder.body_max_bytes;
+    let payload = req.take_payload().map(move |chunk| {
+        if let Ok(bytes) = &chunk {
+            let mut capture = sink.borrow_mut();
+            let room = cap.saturating_sub(capture.data.len()).min(bytes.len());
+            capture.data.extend_from_slice(&bytes[..room]);
+            capture.total += bytes.len();
+        }
+        chunk
+    });
+    req.set_payload(Payload::Stream {
+        payload: Box::pin(payload),
+    });
+
+    let result = next.call(req).await;
+    recording.status = match &result {
+        Ok(resp) => resp.status(),
+        Err(e) => e.as_response_error().status_code(),
+    }
+    .as_u16();
+    let capture = capture.take();
+    recording.body = String::from_utf8_lossy(&capture.data).into_owned();
+    recording.body_bytes = capture.total;
+    recording.body_truncated = capture.total > capture.data.len();
+    if let Err(e) = recorder.write(&recording) {
+        log::warn!(
+            "Failed to record request to {}: {}",
+            recorder.path.display(),
+            e
+        );
+    }
+    result.map(ServiceResponse::map_into_boxed_body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::{test, App, HttpResponse};
+
+    async fn echo_len(body: web::Bytes) -> HttpResponse {
+        HttpResponse::Ok().body(body.len().to_string())
+    }
+
+    #[actix_web::test]
+    async fn test_records_redacted_requests_and_rotates() {
+        let dir = std::env::temp_dir().join(format!("recorder-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("requests.jsonl");
+        let config = Config {
+            record_sample_rate: 1.0,
+            record_file: path.clone(),
+            record_max_bytes: 600,
+            record_body_max_bytes: 8,
+            ..Config::default()
+        };
+        let recorder = Recorder::from_config(&config).unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(recorder))
+                .wrap(from_fn(record_requests))
+                .route("/items", web::post().to(echo_len)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/items?page=2")
+            .insert_header(("authorization", "Bearer s3cret"))
+            .insert_header(("x-request-id", "abc"))
+            .set_payload("0123456789abcdef")
+            .to_request();
+        // The handler still sees the whole body.
+        assert_eq!(test::call_and_read_body(&app, req).await, "16");
+
+        let line = fs::read_to_string(&path).unwrap();
+        let recorded: Value = serde_json::from_str(line.trim()).unwrap();
+        assert_eq!(recorded["method"], "POST");
+        assert_eq!(recorded["path"], "/items");
+        assert_eq!(recorded["query"], "page=2");
+        assert_eq!(recorded["headers"]["authorization"], "<redacted>");
+        assert_eq!(recorded["headers"]["x-request-id"], "abc");
+        assert_eq!(recorded["body"], "01234567");
+        assert_eq!(recorded["body_bytes"], 16);
+        assert_eq!(recorded["body_truncated"], true);
+        assert_eq!(recorded["status"], 200);
+
+        for _ in 0..4 {
+            let req = test::TestRequest::post().uri("/items").to_request();
+            test::call_service(&app, req).await;
+        }
+        assert!(dir.join("requests.jsonl.1").exists());
+        assert!(fs::metadata(&path).unwrap().len() <= 600);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,322 @@
+//! Self-registration for service discovery: with `REGISTRY_URL` set, a
+//! heartbeat carrying this instance's ID, address and readiness goes out
+//! every `HEARTBEAT_INTERVAL_SECS`, and the instance is deregistered on
+//! graceful shutdown. `REGISTRY_KIND` picks the protocol:
+//!
+//! - `http` (default): `POST <url>` with a JSON `{id, service, address,
+//!   port, status}` body per heartbeat, `DELETE <url>/<id>` on shutdown.
+//! - `consul`: a TTL-checked service registered through the agent API
+//!   (`PUT <url>/v1/agent/service/register`), kept alive with
+//!   `PUT <url>/v1/agent/check/update/service:<id>` and removed with
+//!   `PUT <url>/v1/agent/service/deregister/<id>`.
+//!
+//! Registry failures are logged and never take the app down.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use actix_web::web;
+use reqwest::Url;
+use serde_json::json;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use crate::checks::{self, HealthChecks};
+use crate::config::Config;
+use crate::drain::Draining;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RegistryKind {
+    #[default]
+    Http,
+    Consul,
+}
+
+impl FromStr for RegistryKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "http" => Ok(RegistryKind::Http),
+            "consul" => Ok(RegistryKind::Consul),
+            other => Err(format!("unknown registry kind '{}'", other)),
+        }
+    }
+}
+
+struct Registrar {
+    http: reqwest::Client,
+    kind: RegistryKind,
+    base: Url,
+    id: String,
+    service: String,
+    address: String,
+    port: u16,
+    interval: Duration,
+}
+
+impl Registrar {
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base.clone();
+        if let Ok(mut path) = url.path_segments_mut() {
+            path.pop_if_empty().extend(segments);
+        }
+        url
+    }
+
+    /// Consul only: registers the service with a TTL check a few
+    /// heartbeats long, reaped if it stays critical for a minute or more.
+    async fn register(&self) -> Result<(), String> {
+        let ttl = self.interval.as_secs() * 3;
+        let body = json!({
+            "ID": self.id,
+            "Name": self.service,
+            "Address": self.address,
+            "Port": self.port,
+            "Check": {
+                "TTL": format!("{}s", ttl),
+                "DeregisterCriticalServiceAfter": format!("{}s", (ttl * 4).max(60)),
+            },
+        });
+        let url = self.url(&["v1", "agent", "service", "register"]);
+        send(self.http.put(url).json(&body)).await
+    }
+
+    /// `registered` tracks the Consul registration, which is redone after
+    /// any failure in case the agent lost it.
+    async fn heartbeat(
+        &self,
+        registered: &mut bool,
+        ready: bool,
+        status: &str,
+    ) -> Result<(), String> {
+        match self.kind {
+            RegistryKind::Http => {
+                let body = json!({
+                    "id": self.id,
+                    "service": self.service,
+                    "address": self.address,
+                    "port": self.port,
+                    "status": status,
+                });
+                send(self.http.post(self.base.clone()).json(&body)).await
+            }
+            RegistryKind::Consul => {
+                if !*registered {
+                    self.register().await?;
+                    *registered = true;
+                }
+                let check = format!("service:{}", self.id);
+                let body = json!({
+                    "Status": if ready { "passing" } else { "critical" },
+                    "Output": status,
+                });
+                let url = self.url(&["v1", "agent", "check", "update", &check]);
+                let result = send(self.http.put(url).json(&body)).await;
+                *registered = result.is_ok();
+                result
+            }
+        }
+    }
+
+    async fn deregister(&self) -> Result<(), String> {
+        match self.kind {
+            RegistryKind::Http => send(self.http.delete(self.url(&[&self.id]))).await,
+            RegistryKind::Consul => {
+                let url = self.url(&["v1", "agent", "service", "deregister", &self.id]);
+                send(self.http.put(url)).await
+            }
+        }
+    }
+}
+
+async fn send(request: reqwest::RequestBuilder) -> Result<(), String> {
+    let resp = request
+        .timeout(TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| format!("request failed: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    Ok(())
+}
+
+/// A running heartbeat; [`Heartbeat::close`] stops it and deregisters.
+pub struct Heartbeat {
+    shutdown: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl Heartbeat {
+    pub async fn close(self) {
+        let _ = self.shutdown.send(());
+        let _ = self.task.await;
+    }
+}
+
+/// `None` unless `REGISTRY_URL` is set (and valid). `port` is the HTTP port
+/// advertised alongside `REGISTRY_ADDRESS`.
+pub fn start(
+    config: &Config,
+    http: reqwest::Client,
+    checks: web::Data<HealthChecks>,
+    draining: web::Data<Draining>,
+    port: u16,
+) -> Option<Heartbeat> {
+    let base = config.registry_url.as_deref()?;
+    let base = match Url::parse(base) {
+        Ok(url) if !url.cannot_be_a_base() => url,
+        _ => {
+            log::error!(
+                "Service registration disabled: invalid REGISTRY_URL '{}'",
+                base
+            );
+            return None;
+        }
+    };
+    let registrar = Registrar {
+        http,
+        kind: config.registry_kind,
+        base,
+        id: config.instance_id.clone(),
+        service: config.registry_service.clone(),
+        address: config.registry_address.clone(),
+        port,
+        interval: Duration::from_secs(config.heartbeat_interval_secs.max(1)),
+    };
+    log::info!(
+        "Registering {} ({}:{}) with {} every {}s",
+        registrar.id,
+        registrar.address,
+        registrar.port,
+        registrar.base,
+        registrar.interval.as_secs()
+    );
+    let (shutdown, mut closing) = oneshot::channel();
+
+    let task = actix_web::rt::spawn(async move {
+        let mut registered = false;
+        let mut failing = false;
+        loop {
+            let (ready, body) = checks::readiness(&checks, Some(&draining)).await;
+            let status = body["status"].as_str().unwrap_or("unknown");
+            match registrar.heartbeat(&mut registered, ready, status).await {
+                // Log the first failure of a run and the recovery, not every beat.
+                Err(e) if !failing => {
+                    log::warn!("Registry heartbeat to {} failed: {}", registrar.base, e);
+                    failing = true;
+                }
+                Err(_) => {}
+                Ok(()) if failing => {
+                    log::info!("Registry heartbeat to {} recovered", registrar.base);
+                    failing = false;
+                }
+                Ok(()) => {}
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(registrar.interval) => {}
+                _ = &mut closing => break,
+            }
+        }
+        match registrar.deregister().await {
+            Ok(()) => log::info!("Deregistered {} from {}", registrar.id, registrar.base),
+            Err(e) => log::warn!("Deregistering from {} failed: {}", registrar.base, e),
+        }
+    });
+    Some(Heartbeat { shutdown, task })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::VaultToken;
+    use prometheus::IntGaugeVec;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn run_once(kind: RegistryKind, server: &MockServer) {
+        let config = Config {
+            registry_url: Some(server.uri()),
+            registry_kind: kind,
+            registry_service: "rust-api".to_string(),
+            registry_address: "10.0.0.7".to_string(),
+            heartbeat_interval_secs: 3600,
+            instance_id: "pod-7".to_string(),
+            enabled_checks: Vec::new(),
+            ..Config::default()
+        };
+        let gauge =
+            IntGaugeVec::new(prometheus::Opts::new("in_flight", "test"), &["dependency"]).unwrap();
+        let checks = web::Data::new(
+            HealthChecks::new(
+                web::Data::new(config.clone()),
+                web::Data::new(VaultToken::new(None, None)),
+                reqwest::Client::new(),
+                &gauge,
+            )
+            .unwrap(),
+        );
+        let draining = web::Data::new(Draining::default());
+        let heartbeat = start(&config, reqwest::Client::new(), checks, draining, 8004).unwrap();
+        // The first heartbeat goes out straight away.
+        while server.received_requests().await.unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        heartbeat.close().await;
+    }
+
+    #[actix_web::test]
+    async fn test_http_heartbeat_and_deregistration() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/"))
+            .and(body_partial_json(json!({
+                "id": "pod-7",
+                "address": "10.0.0.7",
+                "port": 8004,
+                "status": "ready"
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/pod-7"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        run_once(RegistryKind::Http, &server).await;
+    }
+
+    #[actix_web::test]
+    async fn test_consul_registration() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/v1/agent/service/register"))
+            .and(body_partial_json(
+                json!({"ID": "pod-7", "Name": "rust-api", "Port": 8004}),
+            ))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/v1/agent/check/update/service:pod-7"))
+            .and(body_partial_json(json!({"Status": "passing"})))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/v1/agent/service/deregister/pod-7"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        run_once(RegistryKind::Consul, &server).await;
+    }
+}
@@ -0,0 +1,105 @@
+//! The route table served at `/debug/routes`. actix can't list what it has
+//! registered, so `main` registers its services through [`Routes`], which
+//! records each one's method and path as it goes. Routes added inside
+//! [`Routes::scope`] are listed with the scope's prefix.
+
+use std::sync::OnceLock;
+
+use actix_web::dev::HttpServiceFactory;
+use actix_web::{get, web, HttpResponse, Responder};
+use serde::Serialize;
+
+use crate::config::Config;
+
+/// Where the health routes are served: unversioned and under `/v1`. The
+/// middleware that must let orchestrator probes through exempts all of them.
+pub const HEALTH_PREFIXES: &[&str] = &["/health", "/v1/health"];
+
+/// Whether `path` is a health route.
+pub fn is_health(path: &str) -> bool {
+    HEALTH_PREFIXES.iter().any(|prefix| {
+        path.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Route {
+    pub method: &'static str,
+    pub path: String,
+}
+
+/// Filled by the first worker to register its routes; every worker
+/// registers the same ones.
+#[derive(Debug, Default)]
+pub struct RouteTable(OnceLock<Vec<Route>>);
+
+impl RouteTable {
+    pub fn routes(&self) -> &[Route] {
+        self.0.get().map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Registers services on `cfg`, recording each in the route table.
+pub struct Routes<'a> {
+    cfg: &'a mut web::ServiceConfig,
+    recorded: Vec<Route>,
+}
+
+impl<'a> Routes<'a> {
+    pub fn new(cfg: &'a mut web::ServiceConfig) -> Routes<'a> {
+        Routes {
+            cfg,
+            recorded: Vec::new(),
+        }
+    }
+
+    /// `method` and `path` must match the service's route attribute.
+    pub fn add<F>(&mut self, method: &'static str, path: &'static str, service: F) -> &mut Self
+    where
+        F: HttpServiceFactory + 'static,
+    {
+        self.cfg.service(service);
+        self.recorded.push(Route {
+            method,
+            path: path.to_string(),
+        });
+        self
+    }
+
+    /// Registers the routes `configure` adds under `web::scope(prefix)`.
+    pub fn scope(
+        &mut self,
+        prefix: &'static str,
+        configure: impl FnOnce(&mut Routes),
+    ) -> &mut Self {
+        let mut recorded = Vec::new();
+        self.cfg.service(web::scope(prefix).configure(|cfg| {
+            let mut routes = Routes::new(cfg);
+            configure(&mut routes);
+            recorded = routes.recorded;
+        }));
+        self.recorded
+            .extend(recorded.into_iter().map(|route| Route {
+                method: route.method,
+                path: format!("{}{}", prefix, route.path),
+            }));
+        self
+    }
+
+    pub fn finish(self, table: &RouteTable) {
+        let _ = table.0.set(self.recorded);
+    }
+}
+
+#[get("/debug/routes")]
+pub async fn debug_routes(
+    table: web::Data<RouteTable>,
+    config: Option<web::Data<Config>>,
+) -> impl Responder {
+    let prefix = config.map(|c| c.route_prefix.clone()).unwrap_or_default();
+    HttpResponse::Ok().json(serde_json::json!({
+        "route_prefix": prefix,
+        "routes": table.routes(),
+    }))
+}
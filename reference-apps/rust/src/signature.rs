@@ -0,0 +1,308 @@
+//! HMAC request signing for partners that sign instead of sending bearer
+//! tokens. With `HMAC_SECRET` set, every request outside
+//! `HMAC_EXEMPT_PATHS` must carry:
+//!
+//! - `X-Timestamp`: Unix seconds, within `HMAC_MAX_AGE_SECS` of our clock
+//! - `X-Signature`: hex HMAC-SHA256 of the message below, optionally
+//!   prefixed `sha256=`
+//!
+//! The signed message is the timestamp, method, path and query, and raw
+//! body, joined by newlines (which can't occur in the first three):
+//!
+//! ```text
+//! 1735689600\nPOST\n/v1/orders?notify=true\n{"order":42}
+//! ```
+//!
+//! so a signature is only good for the request it was made for.
+//!
+//! A signature is accepted once; the same request sent again inside the
+//! window is a replay. Seen signatures are kept per instance, so replicas
+//! don't share them. Failures are 401 `invalid_signature`.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::header::HeaderName;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpMessage, ResponseError};
+use futures_util::StreamExt;
+use ring::hmac;
+
+use crate::config::Config;
+use crate::error::ApiError;
+use crate::host_check::is_exempt;
+
+pub const SIGNATURE: HeaderName = HeaderName::from_static("x-signature");
+pub const TIMESTAMP: HeaderName = HeaderName::from_static("x-timestamp");
+
+/// Signatures accepted within the window, with their timestamps.
+#[derive(Default)]
+pub struct SeenSignatures(Mutex<HashMap<Vec<u8>, i64>>);
+
+impl SeenSignatures {
+    /// Records `signature`, or returns false if it was already seen. Entries
+    /// older than the window are dropped as they can no longer be replayed.
+    fn first_use(&self, signature: &[u8], timestamp: i64, now: i64, max_age: u64) -> bool {
+        let mut seen = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        seen.retain(|_, ts| within(now, *ts, max_age));
+        seen.insert(signature.to_vec(), timestamp).is_none()
+    }
+}
+
+/// Whether `timestamp` is at most `max_age` seconds from `now`, either way.
+/// Timestamps come from clients, so the difference may not fit an `i64`.
+fn within(now: i64, timestamp: i64, max_age: u64) -> bool {
+    now.checked_sub(timestamp)
+        .map(i64::unsigned_abs)
+        .is_some_and(|age| age <= max_age)
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            if pair.len() != 2 || !pair.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()
+        })
+        .collect()
+}
+
+fn signed_message(timestamp: &str, method: &str, path_and_query: &str, body: &[u8]) -> Vec<u8> {
+    let mut message = Vec::new();
+    for part in [timestamp, method, path_and_query] {
+        message.extend_from_slice(part.as_bytes());
+        message.push(b'\n');
+    }
+    message.extend_from_slice(body);
+    message
+}
+
+/// What a request signature covers besides its timestamp.
+struct SignedRequest<'a> {
+    method: &'a str,
+    path_and_query: &'a str,
+    body: &'a [u8],
+}
+
+/// Checks the headers against `request`; `ring` compares in constant time.
+fn verify(
+    secret: &str,
+    timestamp: Option<&str>,
+    signature: Option<&str>,
+    request: &SignedRequest,
+    now: i64,
+    max_age: u64,
+    seen: Option<&SeenSignatures>,
+) -> Result<(), ApiError> {
+    let invalid = |reason| ApiError::InvalidSignature { reason };
+    let (Some(timestamp), Some(signature)) = (timestamp, signature) else {
+        return Err(invalid("missing X-Signature or X-Timestamp"));
+    };
+    let ts: i64 = timestamp
+        .trim()
+        .parse()
+        .map_err(|_| invalid("invalid X-Timestamp"))?;
+    if !within(now, ts, max_age) {
+        return Err(invalid("stale X-Timestamp"));
+    }
+    let hex = signature.trim();
+    let tag = decode_hex(hex.strip_prefix("sha256=").unwrap_or(hex))
+        .ok_or_else(|| invalid("X-Signature is not hex"))?;
+
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let message = signed_message(
+        timestamp.trim(),
+        request.method,
+        request.path_and_query,
+        request.body,
+    );
+    hmac::verify(&key, &message, &tag).map_err(|_| invalid("signature mismatch"))?;
+    if seen.is_some_and(|seen| !seen.first_use(&tag, ts, now, max_age)) {
+        return Err(invalid("replayed request"));
+    }
+    Ok(())
+}
+
+pub async fn verify_signature(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let Some(config) = req.app_data::<web::Data<Config>>().cloned() else {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_boxed_body);
+    };
+    let Some(secret) = config.hmac_secret.as_ref() else {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_boxed_body);
+    };
+    if is_exempt(req.path(), &config.hmac_exempt_paths) {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_boxed_body);
+    }
+
+    // The body is buffered to be signed over, then handed on unchanged.
+    let mut body = web::BytesMut::new();
+    let mut payload = req.take_payload();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        if body.len() + chunk.len() > config.max_body_bytes {
+            let e = ApiError::PayloadTooLarge {
+                limit_bytes: config.max_body_bytes,
+            };
+            return Ok(req.into_response(e.error_response()));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    let body = body.freeze();
+
+    let header = |name: &HeaderName| req.headers().get(name).and_then(|v| v.to_str().ok());
+    let request = SignedRequest {
+        method: req.method().as_str(),
+        path_and_query: req.uri().path_and_query().map_or("/", |pq| pq.as_str()),
+        body: &body,
+    };
+    let result = verify(
+        secret.expose(),
+        header(&TIMESTAMP),
+        header(&SIGNATURE),
+        &request,
+        chrono::Utc::now().timestamp(),
+        config.hmac_max_age_secs,
+        req.app_data::<web::Data<SeenSignatures>>()
+            .map(|s| s.get_ref()),
+    );
+    if let Err(e) = result {
+        log::warn!("Rejected signed request to {}: {}", req.path(), e);
+        return Ok(req.into_response(e.error_response()));
+    }
+    req.set_payload(Payload::from(body));
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_boxed_body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Secret;
+    use actix_web::middleware::from_fn;
+    use actix_web::{test, App};
+    use serde_json::Value;
+
+    fn sign(secret: &str, timestamp: i64, path: &str, body: &str) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+        let tag = hmac::sign(
+            &key,
+            &signed_message(&timestamp.to_string(), "POST", path, body.as_bytes()),
+        );
+        let hex: String = tag.as_ref().iter().map(|b| format!("{:02x}", b)).collect();
+        format!("sha256={}", hex)
+    }
+
+    async fn echo(body: web::Bytes) -> web::Bytes {
+        body
+    }
+
+    #[actix_web::test]
+    async fn test_signed_requests() {
+        let config = Config {
+            hmac_secret: Some(Secret("partner-key".to_string())),
+            hmac_max_age_secs: 300,
+            hmac_exempt_paths: vec!["/health".to_string()],
+            max_body_bytes: 1024,
+            ..Config::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(config))
+                .app_data(web::Data::new(SeenSignatures::default()))
+                .wrap(from_fn(verify_signature))
+                .route("/orders", web::post().to(echo))
+                .route("/refunds", web::post().to(echo))
+                .route("/health", web::get().to(echo)),
+        )
+        .await;
+        let now = chrono::Utc::now().timestamp();
+        let body = r#"{"order":42}"#;
+        let request_to = |path: &str, ts: i64, signature: String, body: &'static str| {
+            test::TestRequest::post()
+                .uri(path)
+                .insert_header((TIMESTAMP, ts.to_string()))
+                .insert_header((SIGNATURE, signature))
+                .set_payload(body)
+                .to_request()
+        };
+        let request = |ts, signature, body| request_to("/orders", ts, signature, body);
+        let rejection = |resp: ServiceResponse| async move {
+            assert_eq!(resp.status(), 401);
+            let body: Value = test::read_body_json(resp).await;
+            assert_eq!(body["error"], "invalid_signature");
+            body["message"].as_str().unwrap().to_string()
+        };
+
+        // Valid: the handler sees the original body.
+        let req = request(now, sign("partner-key", now, "/orders", body), body);
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(test::read_body(resp).await, body);
+
+        // The same signed request again is a replay.
+        let req = request(now, sign("partner-key", now, "/orders", body), body);
+        let message = rejection(test::call_service(&app, req).await).await;
+        assert!(message.contains("replayed"), "{}", message);
+
+        // Tampered body.
+        let ts = now - 1;
+        let req = request(
+            ts,
+            sign("partner-key", ts, "/orders", body),
+            r#"{"order":43}"#,
+        );
+        let message = rejection(test::call_service(&app, req).await).await;
+        assert!(message.contains("mismatch"), "{}", message);
+
+        // A correctly signed but old request, replayed after the window.
+        let old = now - 600;
+        let req = request(old, sign("partner-key", old, "/orders", body), body);
+        let message = rejection(test::call_service(&app, req).await).await;
+        assert!(message.contains("stale"), "{}", message);
+
+        // A signature is bound to its path (and method), even with no body.
+        let ts = now - 2;
+        let req = request_to("/refunds", ts, sign("partner-key", ts, "/orders", ""), "");
+        let message = rejection(test::call_service(&app, req).await).await;
+        assert!(message.contains("mismatch"), "{}", message);
+        let req = request_to(
+            "/orders?id=7",
+            ts,
+            sign("partner-key", ts, "/orders", ""),
+            "",
+        );
+        rejection(test::call_service(&app, req).await).await;
+
+        // Far-off timestamps are stale, however far.
+        let req = request(
+            i64::MIN,
+            sign("partner-key", i64::MIN, "/orders", body),
+            body,
+        );
+        let message = rejection(test::call_service(&app, req).await).await;
+        assert!(message.contains("stale"), "{}", message);
+
+        let req = test::TestRequest::post().uri("/orders").to_request();
+        rejection(test::call_service(&app, req).await).await;
+
+        let req = test::TestRequest::get().uri("/health").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+    }
+}
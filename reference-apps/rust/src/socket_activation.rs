@@ -0,0 +1,67 @@
+//! systemd socket activation. When systemd passes us a listening socket
+//! (`LISTEN_PID` is our pid and `LISTEN_FDS` is at least 1), serve on it
+//! instead of binding `HTTP_PORT`, so the socket survives restarts and
+//! connections queue rather than being refused during a handoff.
+
+use std::net::TcpListener;
+
+/// First file descriptor systemd passes (`SD_LISTEN_FDS_START`).
+#[cfg(unix)]
+const LISTEN_FDS_START: i32 = 3;
+
+/// The passed descriptor, if the variables are addressed to process `pid`.
+#[cfg(unix)]
+fn activated_fd(listen_pid: Option<&str>, listen_fds: Option<&str>, pid: u32) -> Option<i32> {
+    let for_us = listen_pid.and_then(|p| p.trim().parse::<u32>().ok()) == Some(pid);
+    let count = listen_fds.and_then(|n| n.trim().parse::<i32>().ok())?;
+    if !for_us || count < 1 {
+        return None;
+    }
+    if count > 1 {
+        log::warn!(
+            "systemd passed {} sockets; serving on the first only",
+            count
+        );
+    }
+    Some(LISTEN_FDS_START)
+}
+
+/// Takes the socket systemd passed, if any. The variables are cleared so
+/// child processes don't try to claim it too.
+#[cfg(unix)]
+pub fn take_listener() -> Option<TcpListener> {
+    use std::env;
+    use std::os::unix::io::FromRawFd;
+
+    let fd = activated_fd(
+        env::var("LISTEN_PID").ok().as_deref(),
+        env::var("LISTEN_FDS").ok().as_deref(),
+        std::process::id(),
+    )?;
+    for name in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        env::remove_var(name);
+    }
+    // SAFETY: systemd guarantees `fd` is an open socket handed to this
+    // process, and nothing else in the process owns it.
+    Some(unsafe { TcpListener::from_raw_fd(fd) })
+}
+
+#[cfg(not(unix))]
+pub fn take_listener() -> Option<TcpListener> {
+    None
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_activated_fd() {
+        assert_eq!(activated_fd(Some("42"), Some("1"), 42), Some(3));
+        assert_eq!(activated_fd(Some("42"), Some("2"), 42), Some(3));
+        assert_eq!(activated_fd(Some("41"), Some("1"), 42), None);
+        assert_eq!(activated_fd(Some("42"), Some("0"), 42), None);
+        assert_eq!(activated_fd(None, Some("1"), 42), None);
+        assert_eq!(activated_fd(Some("42"), None, 42), None);
+    }
+}
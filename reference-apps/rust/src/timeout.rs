@@ -0,0 +1,122 @@
+//! Per-request timeout. Requests that run longer than `REQUEST_TIMEOUT_MS`
+//! get a 504. Callers can ask for a different budget with an
+//! `X-Request-Timeout` header (milliseconds), clamped to
+//! `REQUEST_TIMEOUT_MAX_MS`; malformed values fall back to the default.
+
+use std::time::Duration;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+
+use crate::config::Config;
+use crate::error::ApiError;
+
+pub const REQUEST_TIMEOUT_HEADER: &str = "X-Request-Timeout";
+
+/// Resolves the timeout for a request from its `X-Request-Timeout` value.
+fn effective_timeout(header: Option<&str>, default_ms: u64, max_ms: u64) -> Duration {
+    let ms = header
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|ms| *ms > 0)
+        .map_or(default_ms, |ms| ms.min(max_ms));
+    Duration::from_millis(ms)
+}
+
+pub async fn enforce_timeout(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let Some(config) = req.app_data::<web::Data<Config>>().cloned() else {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_boxed_body);
+    };
+
+    let header = req
+        .headers()
+        .get(REQUEST_TIMEOUT_HEADER)
+        .and_then(|v| v.to_str().ok());
+    let timeout = effective_timeout(
+        header,
+        config.request_timeout_ms,
+        config.request_timeout_max_ms,
+    );
+
+    let route = format!("{} {}", req.method(), req.path());
+    match tokio::time::timeout(timeout, next.call(req)).await {
+        Ok(resp) => resp.map(ServiceResponse::map_into_boxed_body),
+        Err(_) => {
+            let err = ApiError::RequestTimeout {
+                timeout_ms: timeout.as_millis() as u64,
+            };
+            log::warn!("{}: {}", route, err);
+            Err(err.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{self};
+    use actix_web::{App, HttpResponse};
+
+    #[test]
+    fn test_effective_timeout() {
+        let ms = |header| effective_timeout(header, 1_000, 5_000).as_millis();
+        assert_eq!(ms(None), 1_000);
+        assert_eq!(ms(Some("250")), 250);
+        assert_eq!(ms(Some("60000")), 5_000);
+        assert_eq!(ms(Some("soon")), 1_000);
+        assert_eq!(ms(Some("-5")), 1_000);
+        assert_eq!(ms(Some("0")), 1_000);
+    }
+
+    async fn slow() -> HttpResponse {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        HttpResponse::Ok().finish()
+    }
+
+    #[actix_web::test]
+    async fn test_request_timeout_header() {
+        let config = Config {
+            request_timeout_ms: 1_000,
+            request_timeout_max_ms: 2_000,
+            ..Config::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(config))
+                .wrap(from_fn(enforce_timeout))
+                .route("/slow", web::get().to(slow)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/slow").to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::get()
+            .uri("/slow")
+            .insert_header((REQUEST_TIMEOUT_HEADER, "50"))
+            .to_request();
+        let resp = test::try_call_service(&app, req)
+            .await
+            .unwrap_err()
+            .error_response();
+        assert_eq!(resp.status(), 504);
+        let body = actix_web::body::to_bytes(resp.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], "request_timeout");
+        assert_eq!(body["timeout_ms"], 50);
+
+        let req = test::TestRequest::get()
+            .uri("/slow")
+            .insert_header((REQUEST_TIMEOUT_HEADER, "not-a-number"))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+    }
+}
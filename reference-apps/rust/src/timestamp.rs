@@ -0,0 +1,79 @@
+//! Formatting for every timestamp the API emits. `TIMESTAMP_TZ` picks the
+//! zone: `utc` (default), a fixed offset like `+05:30`, or an IANA name like
+//! `Europe/Berlin`. Output is always RFC 3339 with the matching offset.
+
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, FixedOffset, Utc};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TimestampTz {
+    #[default]
+    Utc,
+    Fixed(FixedOffset),
+    Named(chrono_tz::Tz),
+}
+
+impl FromStr for TimestampTz {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("utc") || s == "Z" {
+            return Ok(TimestampTz::Utc);
+        }
+        if let Ok(offset) = FixedOffset::from_str(s) {
+            return Ok(TimestampTz::Fixed(offset));
+        }
+        s.parse::<chrono_tz::Tz>()
+            .map(TimestampTz::Named)
+            .map_err(|_| format!("unknown timezone '{}'", s))
+    }
+}
+
+impl TimestampTz {
+    pub fn format(self, at: DateTime<Utc>) -> String {
+        match self {
+            TimestampTz::Utc => at.to_rfc3339(),
+            TimestampTz::Fixed(offset) => at.with_timezone(&offset).to_rfc3339(),
+            TimestampTz::Named(tz) => at.with_timezone(&tz).to_rfc3339(),
+        }
+    }
+}
+
+static TZ: OnceLock<TimestampTz> = OnceLock::new();
+
+/// Sets the zone for the process; called once at startup. Until then (and
+/// in tests) timestamps are UTC.
+pub fn init(tz: TimestampTz) {
+    let _ = TZ.set(tz);
+}
+
+/// `at` in the configured zone.
+pub fn format(at: DateTime<Utc>) -> String {
+    TZ.get().copied().unwrap_or_default().format(at)
+}
+
+/// The current time in the configured zone.
+pub fn now() -> String {
+    format(Utc::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_tz() {
+        let at = DateTime::parse_from_rfc3339("2026-01-15T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let formatted = |tz: &str| tz.parse::<TimestampTz>().unwrap().format(at);
+
+        assert_eq!(formatted("utc"), "2026-01-15T12:00:00+00:00");
+        assert_eq!(formatted("+05:30"), "2026-01-15T17:30:00+05:30");
+        assert_eq!(formatted("Europe/Berlin"), "2026-01-15T13:00:00+01:00");
+        assert!("Mars/Olympus".parse::<TimestampTz>().is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! TLS certificate expiry checks for downstream services.
+//!
+//! Each `host:port` in `TLS_CHECK_HOSTS` gets a plain TLS handshake and the
+//! peer certificate's `notAfter` is compared against `TLS_EXPIRY_WARN_DAYS`.
+//! Chains are deliberately not verified: internal services use a private CA,
+//! and nothing but the handshake is exchanged with the peer.
+
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::{self, CryptoProvider};
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{self, ClientConfig, DigitallySignedStruct, SignatureScheme};
+use tokio_rustls::TlsConnector;
+
+use crate::timestamp;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Serialize)]
+pub struct CertExpiry {
+    pub host: String,
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub not_after: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub days_until_expiry: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl CertExpiry {
+    pub fn is_healthy(&self) -> bool {
+        self.status == "healthy"
+    }
+}
+
+/// Connects to `target` and reports how long its certificate has left.
+pub async fn check_host(target: &str, warn_days: i64) -> CertExpiry {
+    match peer_not_after(target).await {
+        Ok(not_after) => {
+            let days = days_until(not_after, Utc::now());
+            CertExpiry {
+                host: target.to_string(),
+                status: if days > warn_days {
+                    "healthy"
+                } else {
+                    "unhealthy"
+                }
+                .to_string(),
+                not_after: Some(timestamp::format(not_after)),
+                days_until_expiry: Some(days),
+                error: None,
+            }
+        }
+        Err(e) => CertExpiry {
+            host: target.to_string(),
+            status: "unhealthy".to_string(),
+            not_after: None,
+            days_until_expiry: None,
+            error: Some(e),
+        },
+    }
+}
+
+async fn peer_not_after(target: &str) -> Result<DateTime<Utc>, String> {
+    let (host, port) =
+        split_host_port(target).ok_or_else(|| format!("invalid host:port '{}'", target))?;
+    let server_name = ServerName::try_from(host.clone())
+        .map_err(|e| format!("invalid host '{}': {}", host, e))?;
+
+    let tcp = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect((host.as_str(), port)))
+        .await
+        .map_err(|_| "connect timed out".to_string())?
+        .map_err(|e| format!("connect failed: {}", e))?;
+    let tls = tokio::time::timeout(CONNECT_TIMEOUT, connector().connect(server_name, tcp))
+        .await
+        .map_err(|_| "TLS handshake timed out".to_string())?
+        .map_err(|e| format!("TLS handshake failed: {}", e))?;
+
+    let cert = tls
+        .get_ref()
+        .1
+        .peer_certificates()
+        .and_then(|certs| certs.first())
+        .ok_or_else(|| "peer sent no certificate".to_string())?;
+    let (_, parsed) = x509_parser::parse_x509_certificate(cert.as_ref())
+        .map_err(|e| format!("invalid peer certificate: {}", e))?;
+
+    DateTime::from_timestamp(parsed.validity().not_after.timestamp(), 0)
+        .ok_or_else(|| "certificate notAfter out of range".to_string())
+}
+
+/// Whole days until `not_after`; negative once the certificate has expired.
+fn days_until(not_after: DateTime<Utc>, now: DateTime<Utc>) -> i64 {
+    (not_after - now).num_seconds().div_euclid(86_400)
+}
+
+/// Splits `host:port`, accepting bracketed IPv6 literals like `[::1]:443`.
+fn split_host_port(target: &str) -> Option<(String, u16)> {
+    let (host, port) = target.trim().rsplit_once(':')?;
+    let host = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+    if host.is_empty() || (host.contains(':') && !target.trim().starts_with('[')) {
+        return None;
+    }
+    Some((host.to_string(), port.parse().ok()?))
+}
+
+fn connector() -> TlsConnector {
+    static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+    let config = CONFIG.get_or_init(|| {
+        let provider = Arc::new(crypto::ring::default_provider());
+        let config = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .expect("ring provider supports the default protocol versions")
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(InspectOnly(provider)))
+            .with_no_client_auth();
+        Arc::new(config)
+    });
+    TlsConnector::from(config.clone())
+}
+
+/// Accepts any certificate chain. Handshake signatures are still checked so
+/// the certificate we inspect belongs to the peer we talked to.
+#[derive(Debug)]
+struct InspectOnly(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for InspectOnly {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_host_port() {
+        assert_eq!(
+            split_host_port("vault:8200"),
+            Some(("vault".to_string(), 8200))
+        );
+        assert_eq!(
+            split_host_port("[fe80::1]:6379"),
+            Some(("fe80::1".to_string(), 6379))
+        );
+        assert_eq!(split_host_port("vault"), None);
+        assert_eq!(split_host_port("fe80::1:443"), None);
+        assert_eq!(split_host_port(":443"), None);
+        assert_eq!(split_host_port("vault:https"), None);
+    }
+
+    #[test]
+    fn test_days_until() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let day = chrono::Duration::days(1);
+        assert_eq!(days_until(now + day * 10, now), 10);
+        assert_eq!(
+            days_until(now + day * 10 - chrono::Duration::seconds(1), now),
+            9
+        );
+        assert_eq!(days_until(now - chrono::Duration::seconds(1), now), -1);
+    }
+
+    #[actix_web::test]
+    async fn test_unreachable_host_is_unhealthy() {
+        let result = check_host("127.0.0.1:1", 30).await;
+        assert!(!result.is_healthy());
+        assert!(result.error.is_some());
+        assert!(result.days_until_expiry.is_none());
+    }
+}
@@ -0,0 +1,191 @@
+//! Trailing-slash handling, so `/health` and `/health/` both resolve.
+//!
+//! Routes are registered without a trailing slash. `TRAILING_SLASH_MODE`
+//! picks how a request with one is handled:
+//!
+//! - `trim` (default): the path is rewritten in place by actix's
+//!   `NormalizePath`, which also merges repeated slashes.
+//! - `redirect`: the client gets a 308 to the slash-less path, with repeated
+//!   slashes merged and the query string kept, so caches and clients learn
+//!   the canonical URL. A path that would make the `Location` look
+//!   protocol-relative (`/\host`, which browsers read as `//host`) is left
+//!   alone rather than redirected.
+//!
+//! Behind a gateway that strips `ROUTE_PREFIX`, redirects put it back so the
+//! client is sent to a URL the gateway still routes to us.
+
+use std::str::FromStr;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::LOCATION;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpResponse};
+
+use crate::config::Config;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TrailingSlashMode {
+    #[default]
+    Trim,
+    Redirect,
+}
+
+impl FromStr for TrailingSlashMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "trim" => Ok(TrailingSlashMode::Trim),
+            "redirect" => Ok(TrailingSlashMode::Redirect),
+            other => Err(format!("unknown trailing slash mode '{}'", other)),
+        }
+    }
+}
+
+/// The canonical location for a `path` with a trailing slash: repeated
+/// slashes merged and the trailing ones dropped. `None` if there is nothing
+/// to redirect, or if the result would start with `/\` and so could send
+/// the client off-site.
+fn canonical_location(prefix: &str, path: &str, query: Option<&str>) -> Option<String> {
+    if !path.ends_with('/') {
+        return None;
+    }
+    let mut canonical = String::with_capacity(path.len());
+    for segment in path.split('/').filter(|s| !s.is_empty()) {
+        canonical.push('/');
+        canonical.push_str(segment);
+    }
+    if canonical.is_empty() || canonical.starts_with("/\\") {
+        return None;
+    }
+    Some(match query {
+        Some(q) => format!("{}{}?{}", prefix, canonical, q),
+        None => format!("{}{}", prefix, canonical),
+    })
+}
+
+/// Redirects trailing-slash paths in `redirect` mode; a no-op otherwise.
+pub async fn redirect_trailing_slash(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let query = Some(req.query_string()).filter(|q| !q.is_empty());
+    let redirect = req
+        .app_data::<web::Data<Config>>()
+        .filter(|config| config.trailing_slash_mode == TrailingSlashMode::Redirect)
+        .and_then(|config| canonical_location(&config.route_prefix, req.path(), query));
+
+    match redirect {
+        Some(location) => {
+            let resp = HttpResponse::PermanentRedirect()
+                .insert_header((LOCATION, location))
+                .finish();
+            Ok(req.into_response(resp))
+        }
+        None => next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_boxed_body),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::{from_fn, NormalizePath};
+    use actix_web::test::{self};
+    use actix_web::App;
+
+    async fn ok() -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    #[test]
+    fn test_canonical_location() {
+        assert_eq!(
+            canonical_location("", "/health/", None).as_deref(),
+            Some("/health")
+        );
+        assert_eq!(
+            canonical_location("", "/health//", Some("pretty=true")).as_deref(),
+            Some("/health?pretty=true")
+        );
+        assert_eq!(canonical_location("", "/health", None), None);
+        assert_eq!(canonical_location("", "/", None), None);
+        assert_eq!(
+            canonical_location("", "/a//b/", None).as_deref(),
+            Some("/a/b")
+        );
+    }
+
+    #[test]
+    fn test_canonical_location_stays_on_site() {
+        assert_eq!(
+            canonical_location("", "//evil.com/", None).as_deref(),
+            Some("/evil.com")
+        );
+        assert_eq!(canonical_location("", "/\\evil.com/", None), None);
+        assert_eq!(canonical_location("", "//\\evil.com/", None), None);
+    }
+
+    #[actix_web::test]
+    async fn test_trim_mode_resolves_both_forms() {
+        let app = test::init_service(
+            App::new()
+                .wrap(NormalizePath::trim())
+                .route("/health", web::get().to(ok)),
+        )
+        .await;
+        for uri in ["/health", "/health/"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            assert!(test::call_service(&app, req).await.status().is_success());
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_redirect_mode() {
+        let config = Config {
+            trailing_slash_mode: TrailingSlashMode::Redirect,
+            ..Config::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(config))
+                .wrap(from_fn(redirect_trailing_slash))
+                .route("/health", web::get().to(ok)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/health/?verbose=1")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 308);
+        assert_eq!(resp.headers().get("location").unwrap(), "/health?verbose=1");
+
+        let req = test::TestRequest::get().uri("/health").to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+    }
+
+    #[actix_web::test]
+    async fn test_redirect_keeps_route_prefix() {
+        let config = Config {
+            trailing_slash_mode: TrailingSlashMode::Redirect,
+            route_prefix: "/rust-api".to_string(),
+            ..Config::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(config))
+                .wrap(from_fn(redirect_trailing_slash))
+                .route("/health", web::get().to(ok)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/health/").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 308);
+        assert_eq!(resp.headers().get("location").unwrap(), "/rust-api/health");
+    }
+}
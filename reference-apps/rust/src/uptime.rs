@@ -0,0 +1,65 @@
+//! Process start time and restart tracking for `/info/uptime`.
+
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+
+pub struct Uptime {
+    pub started_at: DateTime<Utc>,
+    started: Instant,
+    /// Restarts recorded in `RESTART_COUNT_FILE`; `None` when unconfigured.
+    pub restart_count: Option<u64>,
+}
+
+impl Uptime {
+    /// Captures the start time and, if a counter file is given, bumps the
+    /// persisted restart count. A missing or unreadable file counts as the
+    /// first boot.
+    pub fn start(restart_count_file: Option<&Path>) -> Uptime {
+        Uptime {
+            started_at: Utc::now(),
+            started: Instant::now(),
+            restart_count: restart_count_file.map(bump_restart_count),
+        }
+    }
+
+    pub fn uptime_seconds(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+}
+
+fn bump_restart_count(path: &Path) -> u64 {
+    let count = match fs::read_to_string(path) {
+        Ok(contents) => contents.trim().parse::<u64>().map_or(0, |n| n + 1),
+        Err(_) => 0,
+    };
+    if let Err(e) = fs::write(path, count.to_string()) {
+        log::warn!(
+            "Failed to persist restart count to {}: {}",
+            path.display(),
+            e
+        );
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restart_count_increments_each_boot() {
+        let path = std::env::temp_dir().join(format!("restart-count-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(Uptime::start(Some(&path)).restart_count, Some(0));
+        assert_eq!(Uptime::start(Some(&path)).restart_count, Some(1));
+        assert_eq!(Uptime::start(Some(&path)).restart_count, Some(2));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "2");
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(Uptime::start(None).restart_count, None);
+    }
+}
@@ -0,0 +1,810 @@
+//! Vault client helpers used by the health checks.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::config::Config;
+
+/// The Vault token, from `VAULT_TOKEN` or, when that is unset, the file
+/// named by `VAULT_TOKEN_FILE` (as written by a Vault Agent sidecar). A
+/// file-backed token is re-read whenever the file's mtime changes.
+pub struct VaultToken {
+    token: RwLock<Option<String>>,
+    file: Option<PathBuf>,
+}
+
+impl VaultToken {
+    pub fn new(env_token: Option<String>, file: Option<PathBuf>) -> VaultToken {
+        if env_token.is_some() {
+            if file.is_some() {
+                log::info!("VAULT_TOKEN is set; ignoring VAULT_TOKEN_FILE");
+            }
+            return VaultToken {
+                token: RwLock::new(env_token),
+                file: None,
+            };
+        }
+
+        let token = file
+            .as_deref()
+            .and_then(|path| match read_token_file(path) {
+                Ok(token) => Some(token),
+                Err(e) => {
+                    log::warn!("{}", e);
+                    None
+                }
+            });
+        VaultToken {
+            token: RwLock::new(token),
+            file,
+        }
+    }
+
+    pub fn get(&self) -> Option<String> {
+        self.token.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Polls the token file for changes until the process exits. Does
+    /// nothing when the token didn't come from a file.
+    pub fn watch(self: Arc<Self>, interval: Duration) {
+        let Some(path) = self.file.clone() else {
+            return;
+        };
+        actix_web::rt::spawn(async move {
+            let mut last_modified = modified(&path);
+            loop {
+                tokio::time::sleep(interval).await;
+                let current = modified(&path);
+                if current == last_modified {
+                    continue;
+                }
+                last_modified = current;
+                match read_token_file(&path) {
+                    Ok(token) => {
+                        *self.token.write().unwrap_or_else(|e| e.into_inner()) = Some(token);
+                        log::info!("Reloaded Vault token from {}", path.display());
+                    }
+                    Err(e) => log::warn!("{}", e),
+                }
+            }
+        });
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn read_token_file(path: &Path) -> Result<String, String> {
+    let token = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read VAULT_TOKEN_FILE {}: {}", path.display(), e))?;
+    let token = token.trim();
+    if token.is_empty() {
+        return Err(format!("VAULT_TOKEN_FILE {} is empty", path.display()));
+    }
+    Ok(token.to_string())
+}
+
+/// Checks that `mount` is listed in `/v1/sys/mounts`.
+///
+/// A Vault that reports healthy can still be missing the KV mount the app
+/// reads secrets from, so readiness verifies it explicitly.
+pub async fn check_mount(
+    http: &reqwest::Client,
+    vault_addr: &str,
+    token: &str,
+    mount: &str,
+    max_bytes: usize,
+) -> Result<(), String> {
+    let resp = http
+        .get(format!("{}/v1/sys/mounts", vault_addr))
+        .header("X-Vault-Token", token)
+        .send()
+        .await
+        .map_err(|e| format!("failed to list Vault mounts: {}", e))?;
+
+    if !resp.status().is_success() {
+        return Err(format!(
+            "failed to list Vault mounts: HTTP {}",
+            resp.status()
+        ));
+    }
+
+    let body = read_body_limited(resp, max_bytes).await?;
+    let mounts: Value = serde_json::from_slice(&body)
+        .map_err(|e| format!("invalid Vault mounts response: {}", e))?;
+
+    if mount_present(&mounts, mount) {
+        Ok(())
+    } else {
+        Err(format!("Vault secret mount '{}' not found", mount))
+    }
+}
+
+/// Checks a Vault Agent caching sidecar at `agent_addr`: it must answer and
+/// hold a valid auto-auth token. Looking up our own token without sending
+/// one makes the agent attach its auto-auth token, so success proves the
+/// agent's token is usable. Returns the token's remaining TTL in seconds.
+pub async fn check_agent(
+    http: &reqwest::Client,
+    agent_addr: &str,
+    max_bytes: usize,
+) -> Result<u64, String> {
+    let resp = http
+        .get(format!(
+            "{}/v1/auth/token/lookup-self",
+            agent_addr.trim_end_matches('/')
+        ))
+        .send()
+        .await
+        .map_err(|e| format!("Vault Agent unreachable: {}", e))?;
+
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(format!(
+            "Vault Agent has no valid token: lookup-self returned HTTP {}",
+            status
+        ));
+    }
+
+    let body = read_body_limited(resp, max_bytes).await?;
+    let lookup: Value = serde_json::from_slice(&body)
+        .map_err(|e| format!("invalid token lookup response: {}", e))?;
+    token_ttl(&lookup).ok_or_else(|| "token lookup response has no ttl".to_string())
+}
+
+fn token_ttl(lookup: &Value) -> Option<u64> {
+    lookup.get("data")?.get("ttl")?.as_u64()
+}
+
+/// The capabilities `token` has on `path`, from `/v1/sys/capabilities-self`.
+pub async fn check_capabilities(
+    http: &reqwest::Client,
+    vault_addr: &str,
+    token: &str,
+    path: &str,
+    max_bytes: usize,
+) -> Result<Vec<String>, String> {
+    let resp = http
+        .post(format!("{}/v1/sys/capabilities-self", vault_addr))
+        .header("X-Vault-Token", token)
+        .json(&serde_json::json!({ "paths": [path] }))
+        .send()
+        .await
+        .map_err(|e| format!("failed to look up token capabilities: {}", e))?;
+
+    if !resp.status().is_success() {
+        return Err(format!(
+            "failed to look up token capabilities: HTTP {}",
+            resp.status()
+        ));
+    }
+
+    let body = read_body_limited(resp, max_bytes).await?;
+    let body: Value = serde_json::from_slice(&body)
+        .map_err(|e| format!("invalid capabilities response: {}", e))?;
+    capabilities_for(&body, path)
+        .ok_or_else(|| format!("capabilities response has no entry for '{}'", path))
+}
+
+/// Reads `path` with response wrapping and unwraps the result, proving the
+/// wrapped-secret delivery path end to end. Wrapping tokens are single-use,
+/// so each call wraps afresh rather than taking one from config. Returns
+/// the secret's key names; the values are dropped here.
+pub async fn check_unwrap(
+    http: &reqwest::Client,
+    vault_addr: &str,
+    token: &str,
+    path: &str,
+    max_bytes: usize,
+) -> Result<Vec<String>, String> {
+    let resp = http
+        .get(format!("{}/v1/{}", vault_addr, path.trim_matches('/')))
+        .header("X-Vault-Token", token)
+        .header("X-Vault-Wrap-TTL", "60s")
+        .send()
+        .await
+        .map_err(|e| format!("failed to read '{}' wrapped: {}", path, e))?;
+    if !resp.status().is_success() {
+        return Err(format!(
+            "failed to read '{}' wrapped: HTTP {}",
+            path,
+            resp.status()
+        ));
+    }
+    let body = read_body_limited(resp, max_bytes).await?;
+    let wrapped: Value =
+        serde_json::from_slice(&body).map_err(|e| format!("invalid wrap response: {}", e))?;
+    let wrapping_token = wrapped
+        .get("wrap_info")
+        .and_then(|w| w.get("token"))
+        .and_then(Value::as_str)
+        .ok_or_else(|| "response was not wrapped".to_string())?;
+
+    let resp = http
+        .post(format!("{}/v1/sys/wrapping/unwrap", vault_addr))
+        .header("X-Vault-Token", wrapping_token)
+        .send()
+        .await
+        .map_err(|e| format!("failed to unwrap: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("failed to unwrap: HTTP {}", resp.status()));
+    }
+    let body = read_body_limited(resp, max_bytes).await?;
+    let unwrapped: Value =
+        serde_json::from_slice(&body).map_err(|e| format!("invalid unwrap response: {}", e))?;
+    secret_keys(&unwrapped).ok_or_else(|| "unwrapped response has no secret data".to_string())
+}
+
+/// A secret that must exist with `keys` before the app serves traffic; a
+/// `REQUIRED_SECRETS` entry `path:key1|key2`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequiredSecret {
+    pub path: String,
+    pub keys: Vec<String>,
+}
+
+impl std::str::FromStr for RequiredSecret {
+    type Err = String;
+
+    fn from_str(entry: &str) -> Result<Self, Self::Err> {
+        let (path, keys) = entry.split_once(':').unwrap_or((entry, ""));
+        let path = path.trim().trim_matches('/');
+        if path.is_empty() {
+            return Err(format!("'{}' has no secret path", entry.trim()));
+        }
+        Ok(RequiredSecret {
+            path: path.to_string(),
+            keys: keys
+                .split('|')
+                .map(str::trim)
+                .filter(|k| !k.is_empty())
+                .map(String::from)
+                .collect(),
+        })
+    }
+}
+
+/// Reads the secret at `path` and returns its key names; the values are
+/// dropped here. Gives up after `timeout`, body included.
+pub async fn read_secret_keys(
+    http: &reqwest::Client,
+    vault_addr: &str,
+    token: &str,
+    path: &str,
+    max_bytes: usize,
+    timeout: Duration,
+) -> Result<Vec<String>, String> {
+    let resp = http
+        .get(format!("{}/v1/{}", vault_addr, path))
+        .header("X-Vault-Token", token)
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(|e| {
+            if e.is_timeout() {
+                format!(
+                    "failed to read secret at {}: timed out after {}ms",
+                    path,
+                    timeout.as_millis()
+                )
+            } else {
+                format!("failed to read secret at {}: {}", path, e)
+            }
+        })?;
+    if !resp.status().is_success() {
+        return Err(format!(
+            "failed to read secret at {}: HTTP {}",
+            path,
+            resp.status()
+        ));
+    }
+    let body = read_body_limited(resp, max_bytes).await?;
+    let secret: Value = serde_json::from_slice(&body)
+        .map_err(|e| format!("invalid secret response for {}: {}", path, e))?;
+    secret_keys(&secret).ok_or_else(|| format!("secret at {} has no data", path))
+}
+
+/// Reads every `REQUIRED_SECRETS` path at startup, each bounded by
+/// `REQUIRED_SECRETS_TIMEOUT_MS` so an unresponsive Vault can't stall boot.
+/// Unreadable secrets and missing keys fail startup under `STRICT_SECRETS`
+/// and are only warned about otherwise.
+pub async fn verify_required_secrets(
+    http: &reqwest::Client,
+    config: &Config,
+    token: Option<&str>,
+) -> Result<(), String> {
+    if config.required_secrets.is_empty() {
+        return Ok(());
+    }
+    let mut problems = Vec::new();
+    match token {
+        None => problems.push("no Vault token to read REQUIRED_SECRETS with".to_string()),
+        Some(token) => {
+            for secret in &config.required_secrets {
+                let keys = read_secret_keys(
+                    http,
+                    &config.vault_addr,
+                    token,
+                    &secret.path,
+                    config.vault_max_response_bytes,
+                    Duration::from_millis(config.required_secrets_timeout_ms),
+                )
+                .await;
+                match keys {
+                    Ok(keys) => problems.extend(
+                        secret
+                            .keys
+                            .iter()
+                            .filter(|key| !keys.contains(key))
+                            .map(|key| format!("secret at {} missing key {}", secret.path, key)),
+                    ),
+                    Err(e) => problems.push(e),
+                }
+            }
+        }
+    }
+    if problems.is_empty() {
+        log::info!(
+            "Verified {} required secret(s)",
+            config.required_secrets.len()
+        );
+        return Ok(());
+    }
+    if config.strict_secrets {
+        return Err(format!("STRICT_SECRETS: {}", problems.join("; ")));
+    }
+    for problem in problems {
+        log::warn!("Required secret check: {}", problem);
+    }
+    Ok(())
+}
+
+/// Key names of a secret read. KV v2 nests the secret under `data.data`,
+/// next to `data.metadata`; KV v1 has it directly under `data`.
+fn secret_keys(body: &Value) -> Option<Vec<String>> {
+    let data = body.get("data")?;
+    let secret = match data.get("data") {
+        Some(inner) if data.get("metadata").is_some() => inner,
+        _ => data,
+    };
+    Some(secret.as_object()?.keys().cloned().collect())
+}
+
+/// Reads one path's capabilities. They're keyed by path, at the top level
+/// and under `data`; older Vault versions only return `capabilities`.
+fn capabilities_for(body: &Value, path: &str) -> Option<Vec<String>> {
+    let list = body
+        .get(path)
+        .or_else(|| body.get("data").and_then(|d| d.get(path)))
+        .or_else(|| body.get("capabilities"))?
+        .as_array()?;
+    Some(
+        list.iter()
+            .filter_map(|c| c.as_str().map(str::to_string))
+            .collect(),
+    )
+}
+
+/// Which `required` capabilities `actual` lacks. `root` grants everything
+/// and `deny` nothing.
+pub fn missing_capabilities(actual: &[String], required: &[String]) -> Vec<String> {
+    let holds = |c: &str| actual.iter().any(|a| a == c);
+    if holds("root") && !holds("deny") {
+        return Vec::new();
+    }
+    required
+        .iter()
+        .filter(|c| holds("deny") || !holds(c))
+        .cloned()
+        .collect()
+}
+
+/// One HA cluster member, as reported by its own `/v1/sys/health`.
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeHealth {
+    pub addr: String,
+    pub reachable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sealed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub standby: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl NodeHealth {
+    fn unreachable(addr: &str, error: String) -> NodeHealth {
+        NodeHealth {
+            addr: addr.to_string(),
+            reachable: false,
+            sealed: None,
+            active: None,
+            standby: None,
+            error: Some(error),
+        }
+    }
+
+    /// Reachable, unsealed and either active or a standby ready to take over.
+    fn is_healthy(&self) -> bool {
+        self.reachable && self.sealed == Some(false)
+    }
+}
+
+/// What a `sys/health` status code says about the node, per Vault's
+/// documented defaults: `Ok` with the role when it can serve requests,
+/// `Err` with the reason when it can't. 429 and 473 only appear when a
+/// request lacks `standbyok`/`perfstandbyok`; DR secondaries (472) don't
+/// serve client requests at all. `None` for codes Vault doesn't document.
+pub fn health_code_state(status: u16) -> Option<Result<&'static str, &'static str>> {
+    Some(match status {
+        200 => Ok("active"),
+        429 => Ok("standby"),
+        473 => Ok("performance_standby"),
+        472 => Err("Vault is a DR secondary"),
+        501 => Err("Vault is not initialized"),
+        503 => Err("Vault is sealed"),
+        _ => return None,
+    })
+}
+
+/// The role a `sys/health` body reports, which is how a standby queried
+/// with `standbyok`/`perfstandbyok` tells itself apart from the active node.
+/// `None` when the body has no `standby` flag.
+pub fn health_body_role(health: &Value) -> Option<&'static str> {
+    let flag = |key| health.get(key).and_then(Value::as_bool);
+    if flag("performance_standby") == Some(true) {
+        return Some("performance_standby");
+    }
+    flag("standby").map(|standby| if standby { "standby" } else { "active" })
+}
+
+/// Probes one node directly, bypassing the load balancer.
+///
+/// `standbyok`/`perfstandbyok` make standbys answer 200 rather than 429/473,
+/// so a non-2xx status means the node itself has a problem; the body still
+/// says which role it has.
+pub async fn probe_node(http: &reqwest::Client, addr: &str, max_bytes: usize) -> NodeHealth {
+    let url = format!(
+        "{}/v1/sys/health?standbyok=true&perfstandbyok=true",
+        addr.trim_end_matches('/')
+    );
+    let resp = match http.get(url).send().await {
+        Ok(resp) => resp,
+        Err(e) => return NodeHealth::unreachable(addr, format!("request failed: {}", e)),
+    };
+    let status = resp.status();
+    let body = match read_body_limited(resp, max_bytes).await {
+        Ok(body) => body,
+        Err(e) => return NodeHealth::unreachable(addr, e),
+    };
+    match serde_json::from_slice::<Value>(&body) {
+        Ok(health) => {
+            let mut node = node_from_health(addr, &health);
+            if !status.is_success() {
+                node.error = Some(format!("HTTP {}", status));
+            }
+            node
+        }
+        Err(e) => NodeHealth::unreachable(addr, format!("invalid health response: {}", e)),
+    }
+}
+
+fn node_from_health(addr: &str, health: &Value) -> NodeHealth {
+    let flag = |key| health.get(key).and_then(Value::as_bool);
+    let standby = flag("standby").unwrap_or(false) || flag("performance_standby").unwrap_or(false);
+    let sealed = flag("sealed");
+    NodeHealth {
+        addr: addr.to_string(),
+        reachable: true,
+        sealed,
+        active: Some(sealed == Some(false) && !standby),
+        standby: Some(standby),
+        error: None,
+    }
+}
+
+/// Overall cluster state: `healthy` when every node is up with exactly one
+/// active, `degraded` when there is an active node but others are down or
+/// sealed, and `unavailable` when no node is active.
+pub fn assess_cluster(nodes: &[NodeHealth]) -> &'static str {
+    let active = nodes.iter().filter(|n| n.active == Some(true)).count();
+    if active == 0 {
+        "unavailable"
+    } else if active == 1 && nodes.iter().all(NodeHealth::is_healthy) {
+        "healthy"
+    } else {
+        "degraded"
+    }
+}
+
+/// Reads a Vault response body, giving up once it exceeds `max_bytes`
+/// (`VAULT_MAX_RESPONSE_BYTES`) rather than buffering whatever a misrouted
+/// endpoint or an oversized secret sends back.
+pub async fn read_body_limited(
+    mut resp: reqwest::Response,
+    max_bytes: usize,
+) -> Result<Vec<u8>, String> {
+    if let Some(len) = resp.content_length() {
+        if len > max_bytes as u64 {
+            return Err(too_large(max_bytes));
+        }
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = resp
+        .chunk()
+        .await
+        .map_err(|e| format!("failed to read Vault response: {}", e))?
+    {
+        append_limited(&mut body, &chunk, max_bytes)?;
+    }
+    Ok(body)
+}
+
+fn append_limited(body: &mut Vec<u8>, chunk: &[u8], max_bytes: usize) -> Result<(), String> {
+    if body.len() + chunk.len() > max_bytes {
+        return Err(too_large(max_bytes));
+    }
+    body.extend_from_slice(chunk);
+    Ok(())
+}
+
+fn too_large(max_bytes: usize) -> String {
+    format!(
+        "Vault response exceeds VAULT_MAX_RESPONSE_BYTES ({} bytes)",
+        max_bytes
+    )
+}
+
+/// Looks up a mount in a `/v1/sys/mounts` response body.
+///
+/// Mount paths are keyed with a trailing slash, either at the top level
+/// (older Vault versions) or under `data`.
+fn mount_present(mounts: &Value, mount: &str) -> bool {
+    let key = format!("{}/", mount.trim_matches('/'));
+    mounts.get(&key).is_some() || mounts.get("data").and_then(|d| d.get(&key)).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{web, App, HttpResponse};
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_required_secret() {
+        let secret: RequiredSecret = "/secret/data/app:username| password".parse().unwrap();
+        assert_eq!(secret.path, "secret/data/app");
+        assert_eq!(secret.keys, vec!["username", "password"]);
+        let bare: RequiredSecret = "secret/data/tls".parse().unwrap();
+        assert!(bare.keys.is_empty());
+        assert!(":key".parse::<RequiredSecret>().is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_verify_required_secrets() {
+        async fn app_secret() -> HttpResponse {
+            HttpResponse::Ok().json(json!({
+                "data": {"data": {"username": "app", "password": "s3cret"}, "metadata": {}}
+            }))
+        }
+        let server = actix_web::HttpServer::new(|| {
+            App::new().route("/v1/secret/data/app", web::get().to(app_secret))
+        })
+        .workers(1)
+        .bind("127.0.0.1:0")
+        .unwrap();
+        let addr = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        let http = reqwest::Client::new();
+        let config = |secrets: &[&str], strict_secrets| Config {
+            vault_addr: format!("http://{}", addr),
+            vault_max_response_bytes: 4096,
+            required_secrets: secrets.iter().map(|s| s.parse().unwrap()).collect(),
+            strict_secrets,
+            ..Config::default()
+        };
+
+        let present = config(&["secret/data/app:username|password"], true);
+        assert!(verify_required_secrets(&http, &present, Some("t"))
+            .await
+            .is_ok());
+
+        let missing = config(&["secret/data/app:api_key", "secret/data/gone:x"], true);
+        let err = verify_required_secrets(&http, &missing, Some("t"))
+            .await
+            .unwrap_err();
+        assert!(err.contains("secret at secret/data/app missing key api_key"));
+        assert!(err.contains("failed to read secret at secret/data/gone: HTTP 404"));
+        assert!(!err.contains("s3cret"));
+        assert!(verify_required_secrets(&http, &missing, None)
+            .await
+            .is_err());
+
+        let lenient = config(&["secret/data/app:api_key"], false);
+        assert!(verify_required_secrets(&http, &lenient, Some("t"))
+            .await
+            .is_ok());
+
+        handle.stop(false).await;
+    }
+
+    #[actix_web::test]
+    async fn test_required_secrets_time_out() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let hung = MockServer::start().await;
+        Mock::given(wiremock::matchers::path("/v1/secret/data/app"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .mount(&hung)
+            .await;
+        let config = Config {
+            vault_addr: hung.uri(),
+            required_secrets: vec!["secret/data/app".parse().unwrap()],
+            strict_secrets: true,
+            required_secrets_timeout_ms: 100,
+            ..Config::default()
+        };
+
+        let start = std::time::Instant::now();
+        let err = verify_required_secrets(&reqwest::Client::new(), &config, Some("t"))
+            .await
+            .unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(err.contains("failed to read secret at secret/data/app: timed out after 100ms"));
+    }
+
+    #[test]
+    fn test_token_ttl() {
+        assert_eq!(token_ttl(&json!({"data": {"ttl": 2764}})), Some(2764));
+        assert_eq!(token_ttl(&json!({"data": {}})), None);
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let path = "secret/data/app";
+        let body = json!({"capabilities": ["read"], "secret/data/app": ["read", "list"]});
+        assert_eq!(
+            capabilities_for(&body, path),
+            Some(vec!["read".to_string(), "list".to_string()])
+        );
+        assert_eq!(
+            capabilities_for(&json!({"data": {"secret/data/app": ["deny"]}}), path),
+            Some(vec!["deny".to_string()])
+        );
+        assert_eq!(capabilities_for(&json!({}), path), None);
+
+        let owned = |list: &[&str]| list.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        let required = owned(&["read", "update"]);
+        assert_eq!(
+            missing_capabilities(&owned(&["read", "list"]), &required),
+            owned(&["update"])
+        );
+        assert!(missing_capabilities(&owned(&["root"]), &required).is_empty());
+        assert_eq!(missing_capabilities(&owned(&["deny"]), &required), required);
+    }
+
+    #[test]
+    fn test_secret_keys() {
+        let v2 = json!({"data": {"data": {"user": "a", "pass": "b"}, "metadata": {"version": 3}}});
+        let mut keys = secret_keys(&v2).unwrap();
+        keys.sort();
+        assert_eq!(keys, vec!["pass", "user"]);
+        let v1 = json!({"data": {"data": "x", "user": "a"}});
+        assert_eq!(secret_keys(&v1).unwrap().len(), 2);
+        assert_eq!(secret_keys(&json!({})), None);
+    }
+
+    #[test]
+    fn test_health_body_role() {
+        let role = |body| health_body_role(&body);
+        assert_eq!(role(json!({"standby": false})), Some("active"));
+        assert_eq!(role(json!({"standby": true})), Some("standby"));
+        assert_eq!(
+            role(json!({"standby": true, "performance_standby": true})),
+            Some("performance_standby")
+        );
+        assert_eq!(role(json!({"sealed": false})), None);
+    }
+
+    #[test]
+    fn test_health_code_state() {
+        assert_eq!(health_code_state(200), Some(Ok("active")));
+        assert_eq!(health_code_state(429), Some(Ok("standby")));
+        assert_eq!(health_code_state(473), Some(Ok("performance_standby")));
+        assert_eq!(health_code_state(503), Some(Err("Vault is sealed")));
+        assert_eq!(
+            health_code_state(501),
+            Some(Err("Vault is not initialized"))
+        );
+        assert!(health_code_state(472).unwrap().is_err());
+        assert_eq!(health_code_state(500), None);
+    }
+
+    #[test]
+    fn test_assess_cluster() {
+        let active = node_from_health(
+            "http://vault-1:8200",
+            &json!({"sealed": false, "standby": false}),
+        );
+        let standby = node_from_health(
+            "http://vault-2:8200",
+            &json!({"sealed": false, "standby": true}),
+        );
+        let perf = node_from_health(
+            "http://vault-3:8200",
+            &json!({"sealed": false, "standby": false, "performance_standby": true}),
+        );
+        let sealed = node_from_health(
+            "http://vault-3:8200",
+            &json!({"sealed": true, "standby": true}),
+        );
+        let down = NodeHealth::unreachable("http://vault-3:8200", "timed out".to_string());
+
+        assert_eq!(active.active, Some(true));
+        assert_eq!(perf.standby, Some(true));
+        assert_eq!(sealed.active, Some(false));
+
+        assert_eq!(
+            assess_cluster(&[active.clone(), standby.clone(), perf]),
+            "healthy"
+        );
+        assert_eq!(
+            assess_cluster(&[active.clone(), standby.clone(), sealed]),
+            "degraded"
+        );
+        assert_eq!(assess_cluster(&[active, down]), "degraded");
+        assert_eq!(assess_cluster(&[standby]), "unavailable");
+    }
+
+    #[test]
+    fn test_append_limited() {
+        let mut body = Vec::new();
+        assert!(append_limited(&mut body, b"{\"data\":", 16).is_ok());
+        assert!(append_limited(&mut body, b"{}}", 16).is_ok());
+        assert_eq!(body, b"{\"data\":{}}");
+
+        let err = append_limited(&mut body, b"more than fits", 16).unwrap_err();
+        assert!(err.contains("VAULT_MAX_RESPONSE_BYTES"));
+        assert_eq!(body.len(), 11);
+    }
+
+    #[test]
+    fn test_token_precedence_and_file() {
+        let path = std::env::temp_dir().join(format!("vault-token-{}", std::process::id()));
+        fs::write(&path, "s.from-file\n").unwrap();
+
+        let token = VaultToken::new(Some("s.from-env".to_string()), Some(path.clone()));
+        assert_eq!(token.get().as_deref(), Some("s.from-env"));
+
+        let token = VaultToken::new(None, Some(path.clone()));
+        assert_eq!(token.get().as_deref(), Some("s.from-file"));
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(VaultToken::new(None, Some(path)).get(), None);
+        assert_eq!(VaultToken::new(None, None).get(), None);
+    }
+
+    #[test]
+    fn test_mount_present() {
+        let mounts = json!({
+            "secret/": {"type": "kv"},
+            "data": {"secret/": {"type": "kv"}, "sys/": {"type": "system"}}
+        });
+        assert!(mount_present(&mounts, "secret"));
+        assert!(mount_present(&mounts, "/secret/"));
+        assert!(mount_present(&json!({"data": {"kv/": {}}}), "kv"));
+        assert!(!mount_present(&mounts, "kv"));
+    }
+}